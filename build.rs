@@ -0,0 +1,29 @@
+use std::fs;
+use std::process::Command;
+
+fn main() {
+    let sha = Command::new("git")
+        .args(&["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|sha| sha.trim().to_owned())
+        .unwrap_or_else(|| "unknown".into());
+
+    println!("cargo:rustc-env=SONMHUB_GIT_SHA={}", sha);
+    // New commits move the branch HEAD points to, rather than HEAD itself,
+    // and refs may be packed.
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/packed-refs");
+    if let Some(reference) = head_reference() {
+        println!("cargo:rerun-if-changed=.git/{}", reference);
+    }
+}
+
+/// Returns the path of the ref HEAD points to, like `refs/heads/master`,
+/// unless HEAD is detached.
+fn head_reference() -> Option<String> {
+    let head = fs::read_to_string(".git/HEAD").ok()?;
+    head.trim().splitn(2, "ref: ").nth(1).map(|v| v.to_owned())
+}
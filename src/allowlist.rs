@@ -0,0 +1,374 @@
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File};
+use std::io::Write;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+use futures::prelude::*;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json;
+use tokio::timer::Interval;
+
+use config::AllowlistConfig;
+use github::{Client, Meta};
+
+/// Maps IPv4-mapped IPv6 addresses, which are seen by listeners bound on
+/// `::`, to plain IPv4 addresses.
+fn normalize(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(v6) => {
+            let segments = v6.segments();
+            if segments[..5].iter().all(|v| *v == 0) && segments[5] == 0xffff {
+                v6.to_ipv4().map(IpAddr::V4).unwrap_or(addr)
+            } else {
+                addr
+            }
+        }
+        IpAddr::V4(..) => addr,
+    }
+}
+
+/// Network address range, like `192.30.252.0/22`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.addr, normalize(addr)) {
+            (IpAddr::V4(net), IpAddr::V4(addr)) => {
+                let mask = if self.prefix == 0 { 0 } else { !0u32 << (32 - self.prefix) };
+                u32::from(net) & mask == u32::from(addr) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(addr)) => {
+                let mask = if self.prefix == 0 { 0 } else { !0u128 << (128 - self.prefix) };
+                u128::from(net) & mask == u128::from(addr) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    /// Parses a range in the CIDR notation. An address without the prefix
+    /// length is a range of exactly one address.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut parts = value.splitn(2, '/');
+        let addr = parts
+            .next()
+            .and_then(|v| v.parse::<IpAddr>().ok())
+            .map(normalize)
+            .ok_or_else(|| format!("invalid address in `{}`", value))?;
+
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match parts.next() {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|v| *v <= max)
+                .ok_or_else(|| format!("invalid prefix length in `{}`", value))?,
+            None => max,
+        };
+
+        Ok(Self { addr, prefix })
+    }
+}
+
+impl Display for Cidr {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(fmt, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+impl<'de> Deserialize<'de> for Cidr {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        let value: String = Deserialize::deserialize(de)?;
+        value.parse().map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for Cidr {
+    fn serialize<S: Serializer>(&self, se: S) -> Result<S::Ok, S::Error> {
+        se.collect_str(self)
+    }
+}
+
+fn parse_meta(meta: &Meta) -> Vec<Cidr> {
+    meta.hooks
+        .iter()
+        .filter_map(|v| match v.parse() {
+            Ok(cidr) => Some(cidr),
+            Err(err) => {
+                warn!("skipped GitHub hook range: {}", err);
+                None
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+struct Inner {
+    cfg: AllowlistConfig,
+    /// GitHub webhook ranges from the `/meta` API.
+    meta: RwLock<Vec<Cidr>>,
+}
+
+/// Source address allowlist of the webhook endpoint.
+///
+/// This is a defense in depth, since deliveries are authenticated with HMAC
+/// signatures anyway.
+#[derive(Clone, Debug)]
+pub struct Allowlist {
+    inner: Arc<Inner>,
+}
+
+impl Allowlist {
+    /// Constructs a new allowlist, restoring GitHub ranges from the cache,
+    /// if any.
+    pub fn new(cfg: AllowlistConfig) -> Self {
+        let meta = cfg
+            .cache()
+            .filter(|_| cfg.github_meta())
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str::<Meta>(&content).ok())
+            .map(|meta| parse_meta(&meta))
+            .unwrap_or_default();
+
+        let inner = Inner {
+            cfg,
+            meta: RwLock::new(meta),
+        };
+
+        Self { inner: Arc::new(inner) }
+    }
+
+    /// Returns `false` if hooks are accepted from anywhere.
+    pub fn is_enabled(&self) -> bool {
+        !self.inner.cfg.cidrs().is_empty() || self.inner.cfg.github_meta()
+    }
+
+    fn is_trusted(&self, addr: IpAddr) -> bool {
+        self.inner.cfg.trusted_proxies().iter().any(|v| v.contains(addr))
+    }
+
+    /// Determines the client address of the request received from the given
+    /// peer, which is `None` for Unix sockets.
+    ///
+    /// `X-Forwarded-For` values, one per header line in order, are only
+    /// taken into account when the peer is a trusted proxy, in which case the
+    /// client is the rightmost untrusted address, since addresses to the left
+    /// may be forged by the client itself. Unix socket peers are local
+    /// proxies, so they are trusted. Returns `None` if the client can not be
+    /// determined.
+    pub fn client(&self, peer: Option<IpAddr>, forwarded: &[&str]) -> Option<IpAddr> {
+        let peer = peer.map(normalize);
+        match peer {
+            Some(peer) if !self.is_trusted(peer) => return Some(peer),
+            Some(..) | None => {}
+        }
+
+        let hops = forwarded
+            .iter()
+            .flat_map(|v| v.split(','))
+            .map(|v| v.trim().parse::<IpAddr>().ok().map(normalize))
+            .collect::<Option<Vec<_>>>()?;
+
+        match hops.iter().rev().find(|v| !self.is_trusted(**v)) {
+            Some(client) => Some(*client),
+            None => hops.first().cloned().or(peer),
+        }
+    }
+
+    pub fn allows(&self, addr: IpAddr) -> bool {
+        if !self.is_enabled() {
+            return true;
+        }
+
+        let inner = &self.inner;
+        inner.cfg.cidrs().iter().any(|v| v.contains(addr)) ||
+            inner.meta.read().expect("allowlist lock is poisoned").iter().any(|v| v.contains(addr))
+    }
+
+    /// Periodically refreshes GitHub webhook ranges, if enabled.
+    #[async]
+    pub fn watch(self, client: Client) -> Result<(), ()> {
+        if !self.inner.cfg.github_meta() {
+            return Ok(());
+        }
+
+        let ticks = Interval::new(Instant::now(), self.inner.cfg.refresh_interval())
+            .map_err(|err| error!("allowlist timer failed: {}", err));
+
+        #[async]
+        for _ in ticks {
+            let meta = match await!(client.meta().then(Ok::<_, ()>))? {
+                Ok(meta) => meta,
+                Err(err) => {
+                    warn!("failed to fetch GitHub hook ranges: {:?}", err);
+                    continue;
+                }
+            };
+
+            let ranges = parse_meta(&meta);
+            debug!("GitHub delivers hooks from {} ranges", ranges.len());
+            *self.inner.meta.write().expect("allowlist lock is poisoned") = ranges;
+
+            if let Some(path) = self.inner.cfg.cache() {
+                let result = serde_json::to_string(&meta)
+                    .map_err(Into::into)
+                    .and_then(|content| File::create(path).and_then(|mut file| file.write_all(content.as_bytes())));
+
+                if let Err(err) = result {
+                    warn!("failed to cache GitHub hook ranges in `{}`: {}", path.display(), err);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::IpAddr;
+
+    use serde_yaml;
+
+    use super::{Allowlist, Cidr};
+
+    fn cidr(value: &str) -> Cidr {
+        value.parse().unwrap()
+    }
+
+    fn addr(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    fn allowlist(cfg: &str) -> Allowlist {
+        Allowlist::new(serde_yaml::from_str(cfg).unwrap())
+    }
+
+    #[test]
+    fn ipv4_ranges() {
+        let cases = [
+            ("192.30.252.0/22", "192.30.252.1", true),
+            ("192.30.252.0/22", "192.30.255.255", true),
+            ("192.30.252.0/22", "192.31.0.0", false),
+            ("192.30.252.0/22", "192.30.251.255", false),
+            ("10.0.0.1/32", "10.0.0.1", true),
+            ("10.0.0.1/32", "10.0.0.2", false),
+            ("10.0.0.1", "10.0.0.1", true),
+            ("10.0.0.1", "10.0.0.2", false),
+            ("0.0.0.0/0", "203.0.113.7", true),
+            ("0.0.0.0/0", "2001:db8::1", false),
+            ("192.30.252.0/22", "::ffff:192.30.252.1", true),
+        ];
+
+        for &(range, value, expected) in &cases {
+            assert_eq!(expected, cidr(range).contains(addr(value)), "{} in {}", value, range);
+        }
+    }
+
+    #[test]
+    fn ipv6_ranges() {
+        let cases = [
+            ("2001:db8::/32", "2001:db8::1", true),
+            ("2001:db8::/32", "2001:db8:ffff:ffff::1", true),
+            ("2001:db8::/32", "2001:db9::1", false),
+            ("2001:db8::1/128", "2001:db8::1", true),
+            ("2001:db8::1/128", "2001:db8::2", false),
+            ("::/0", "2001:db8::1", true),
+            ("::/0", "10.0.0.1", false),
+        ];
+
+        for &(range, value, expected) in &cases {
+            assert_eq!(expected, cidr(range).contains(addr(value)), "{} in {}", value, range);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_ranges() {
+        let cases = [
+            "",
+            "/8",
+            "10.0.0.0/",
+            "10.0.0.0/33",
+            "10.0.0.0/-1",
+            "10.0.0/8",
+            "2001:db8::/129",
+            "::ffff:10.0.0.0/104",
+            "example.com/8",
+            "10.0.0.0/8/8",
+        ];
+
+        for value in &cases {
+            assert!(value.parse::<Cidr>().is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn ignores_forwarded_from_untrusted_peers() {
+        let allowlist = allowlist("trusted_proxies: [10.0.0.0/8]");
+
+        assert_eq!(Some(addr("203.0.113.7")), allowlist.client(Some(addr("203.0.113.7")), &["192.30.252.1"]));
+        assert_eq!(Some(addr("203.0.113.7")), allowlist.client(Some(addr("::ffff:203.0.113.7")), &[]));
+    }
+
+    #[test]
+    fn selects_rightmost_untrusted_hop() {
+        let allowlist = allowlist("trusted_proxies: [10.0.0.0/8]");
+        let peer = Some(addr("10.0.0.1"));
+
+        assert_eq!(Some(addr("192.30.252.1")), allowlist.client(peer, &["192.30.252.1"]));
+        assert_eq!(
+            Some(addr("192.30.252.1")),
+            allowlist.client(peer, &["1.2.3.4, 192.30.252.1, 10.0.0.2"])
+        );
+        // Only the proxies themselves forwarded the request.
+        assert_eq!(Some(addr("10.0.0.3")), allowlist.client(peer, &["10.0.0.3, 10.0.0.2"]));
+        assert_eq!(peer, allowlist.client(peer, &[]));
+        // Unix socket peers are trusted.
+        assert_eq!(Some(addr("192.30.252.1")), allowlist.client(None, &["192.30.252.1"]));
+        assert_eq!(None, allowlist.client(None, &[]));
+    }
+
+    #[test]
+    fn joins_multiple_forwarded_headers() {
+        let allowlist = allowlist("trusted_proxies: [10.0.0.0/8]");
+        let peer = Some(addr("10.0.0.1"));
+
+        assert_eq!(
+            Some(addr("192.30.252.1")),
+            allowlist.client(peer, &["1.2.3.4", "192.30.252.1, 10.0.0.2"])
+        );
+        assert_eq!(Some(addr("192.30.252.1")), allowlist.client(peer, &["1.2.3.4, 10.0.0.3", "192.30.252.1"]));
+    }
+
+    #[test]
+    fn rejects_malformed_forwarded() {
+        let allowlist = allowlist("trusted_proxies: [10.0.0.0/8]");
+        let peer = Some(addr("10.0.0.1"));
+
+        assert_eq!(None, allowlist.client(peer, &["192.30.252.1, unknown"]));
+        assert_eq!(None, allowlist.client(peer, &["192.30.252.1", ""]));
+    }
+
+    #[test]
+    fn allows_configured_ranges() {
+        let open = allowlist("{}");
+        assert!(!open.is_enabled());
+        assert!(open.allows(addr("203.0.113.7")));
+
+        let allowlist = allowlist("cidrs: [192.30.252.0/22, \"2001:db8::/32\"]");
+        assert!(allowlist.is_enabled());
+        assert!(allowlist.allows(addr("192.30.252.1")));
+        assert!(allowlist.allows(addr("2001:db8::1")));
+        assert!(!allowlist.allows(addr("203.0.113.7")));
+    }
+}
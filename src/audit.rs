@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde_json;
+
+use bot::policy::{Decision, Mode};
+use github::PullRequest;
+
+/// An error that may occur while opening or querying the audit log.
+#[derive(Debug)]
+pub enum AuditError {
+    Open(PathBuf, io::Error),
+    Read(PathBuf, io::Error),
+}
+
+impl Display for AuditError {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        match self {
+            AuditError::Open(path, err) => write!(fmt, "failed to open audit log `{}`: {}", path.display(), err),
+            AuditError::Read(path, err) => write!(fmt, "failed to read audit log `{}`: {}", path.display(), err),
+        }
+    }
+}
+
+impl error::Error for AuditError {}
+
+/// Action the bot has taken on a decision.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    None,
+    /// Merged the PR.
+    Merge,
+    /// Merged the base branch into the PR branch.
+    Update,
+    /// Published the commit status of the PR head.
+    Status,
+    /// Dismissed a stale approval.
+    Dismiss,
+    /// Added or removed a label.
+    Label,
+    /// Posted or edited a comment, other than a shadow mode one.
+    Comment,
+    /// Reacted to a command comment.
+    React,
+    /// Requested reviews.
+    RequestReviewers,
+    /// Announced an action skipped in the shadow mode.
+    Shadow,
+}
+
+impl Action {
+    /// Names of all actions, as accepted by the `audit` subcommand.
+    pub const NAMES: &'static [&'static str] = &[
+        "none",
+        "merge",
+        "update",
+        "status",
+        "dismiss",
+        "label",
+        "comment",
+        "react",
+        "request_reviewers",
+        "shadow",
+    ];
+}
+
+/// What the bot has seen when deciding on a PR.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Inputs {
+    pub title: String,
+    pub mergeable: String,
+    pub labels: Vec<String>,
+    /// Status contexts of the head with their states.
+    pub statuses: Vec<String>,
+    /// Reviews with their authors, states and commits, in chronological
+    /// order.
+    pub reviews: Vec<String>,
+}
+
+impl<'a> From<&'a PullRequest> for Inputs {
+    fn from(pr: &'a PullRequest) -> Self {
+        let statuses = pr.statuses()
+            .iter()
+            .map(|v| format!("{}: {:?}", v.context, v.state))
+            .collect();
+
+        let reviews = pr.reviews()
+            .iter()
+            .map(|v| {
+                let author = v.author.as_ref().map(|v| v.login.as_str()).unwrap_or("ghost");
+                let commit = v.commit.as_ref().map(|v| v.oid.as_str()).unwrap_or("-");
+                format!("{} ({}): {:?} at {}", author, v.author_association, v.state, commit)
+            })
+            .collect();
+
+        Self {
+            title: pr.title.clone(),
+            mergeable: format!("{:?}", pr.mergeable),
+            labels: pr.labels().map(|v| v.to_owned()).collect(),
+            statuses,
+            reviews,
+        }
+    }
+}
+
+/// A single decision of the bot on a PR.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Record {
+    /// Time of the decision in RFC 3339.
+    pub timestamp: String,
+    /// Repository full name.
+    pub repository: String,
+    pub number: u64,
+    pub head: String,
+    pub mode: Mode,
+    pub inputs: Inputs,
+    /// Whether the policy allows to merge the PR.
+    pub mergeable: bool,
+    /// Policy verdict, explaining why the PR is not merged.
+    pub verdict: String,
+    pub action: Action,
+    /// GitHub response to the action, `None` if nothing was requested.
+    pub response: Option<String>,
+}
+
+impl Record {
+    pub fn new(repository: &str, pr: &PullRequest, decision: &Decision, mode: Mode) -> Self {
+        Self {
+            timestamp: Utc::now().to_rfc3339(),
+            repository: repository.into(),
+            number: pr.number,
+            head: pr.head_ref_oid.clone(),
+            mode,
+            inputs: Inputs::from(pr),
+            mergeable: decision.is_merge(),
+            verdict: decision.to_string(),
+            action: Action::None,
+            response: None,
+        }
+    }
+
+    /// Constructs a record of an action taken outside of merge decisions,
+    /// like requesting reviewers of a new PR, with its reason as the verdict.
+    /// The head is empty if unknown.
+    pub fn event(repository: &str, number: u64, head: &str, mode: Mode, reason: String) -> Self {
+        Self {
+            timestamp: Utc::now().to_rfc3339(),
+            repository: repository.into(),
+            number,
+            head: head.into(),
+            mode,
+            inputs: Inputs::default(),
+            mergeable: false,
+            verdict: reason,
+            action: Action::None,
+            response: None,
+        }
+    }
+
+    pub fn with_action(mut self, action: Action, response: String) -> Self {
+        self.action = action;
+        self.response = Some(response);
+        self
+    }
+}
+
+impl Display for Record {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(
+            fmt,
+            "{} {}#{} {} {:?} {:?}: {}",
+            self.timestamp,
+            self.repository,
+            self.number,
+            &self.head[..self.head.len().min(8)],
+            self.mode,
+            self.action,
+            self.verdict
+        )?;
+
+        if let Some(response) = &self.response {
+            write!(fmt, " -> {}", response)?;
+        }
+
+        Ok(())
+    }
+}
+
+struct Inner {
+    log: Mutex<File>,
+    /// Last verdicts by repository and PR number along with the head they
+    /// were given at.
+    verdicts: Mutex<HashMap<(String, u64), (String, String, Mode)>>,
+}
+
+/// Append-only stream of bot decisions, one JSON record per line.
+///
+/// Decisions without actions are recorded only when the verdict or the head
+/// changes, since PRs are evaluated over and over again.
+#[derive(Clone)]
+pub struct Audit {
+    inner: Arc<Inner>,
+}
+
+impl Audit {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, AuditError> {
+        let path = path.as_ref();
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| AuditError::Open(path.into(), err))?;
+
+        let inner = Inner {
+            log: Mutex::new(log),
+            verdicts: Mutex::new(HashMap::new()),
+        };
+
+        Ok(Self { inner: Arc::new(inner) })
+    }
+
+    /// Appends the record, failures are only logged, because the bot must
+    /// keep serving repositories regardless.
+    pub fn record(&self, record: &Record) {
+        if record.action == Action::None {
+            let key = (record.repository.clone(), record.number);
+            let value = (record.head.clone(), record.verdict.clone(), record.mode);
+
+            let mut verdicts = self.inner.verdicts.lock().expect("audit lock is poisoned");
+            if verdicts.get(&key) == Some(&value) {
+                return;
+            }
+            verdicts.insert(key, value);
+        }
+
+        if let Err(err) = self.write(record) {
+            error!("failed to write audit log: {}", err);
+        }
+    }
+
+    /// Forgets last verdicts of the repository PRs that are no longer open.
+    pub fn retain_open(&self, repository: &str, open: &[u64]) {
+        self.inner
+            .verdicts
+            .lock()
+            .expect("audit lock is poisoned")
+            .retain(|(name, number), _| name != repository || open.contains(number));
+    }
+
+    fn write(&self, record: &Record) -> Result<(), io::Error> {
+        let line = serde_json::to_string(record)?;
+
+        let mut log = self.inner.log.lock().expect("audit lock is poisoned");
+        writeln!(log, "{}", line)?;
+        log.flush()
+    }
+}
+
+/// Conditions records must match, all of them if given.
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    pub repository: Option<String>,
+    pub number: Option<u64>,
+    /// SHA prefix of the head.
+    pub head: Option<String>,
+    pub action: Option<Action>,
+    pub since: Option<DateTime<Utc>>,
+}
+
+impl Filter {
+    pub fn matches(&self, record: &Record) -> bool {
+        self.repository.as_ref().map_or(true, |v| v.eq_ignore_ascii_case(&record.repository)) &&
+            self.number.map_or(true, |v| v == record.number) &&
+            self.head.as_ref().map_or(true, |v| record.head.starts_with(v.as_str())) &&
+            self.action.map_or(true, |v| v == record.action) &&
+            self.since.map_or(true, |v| {
+                DateTime::parse_from_rfc3339(&record.timestamp).map(|t| t >= v).unwrap_or(false)
+            })
+    }
+}
+
+/// Reads records of the audit log at the given path matching the filter.
+///
+/// Lines that are not records, like ones torn by a crash in the middle of a
+/// write, are skipped with a warning printed to stderr.
+pub fn query<P: AsRef<Path>>(path: P, filter: &Filter) -> Result<Vec<Record>, AuditError> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|err| AuditError::Open(path.into(), err))?;
+
+    let mut records = Vec::new();
+    for (id, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|err| AuditError::Read(path.into(), err))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record: Record = match serde_json::from_str(&line) {
+            Ok(record) => record,
+            Err(err) => {
+                eprintln!("skipped invalid record at `{}:{}`: {}", path.display(), id + 1, err);
+                continue;
+            }
+        };
+
+        if filter.matches(&record) {
+            records.push(record);
+        }
+    }
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+
+    use super::{query, Action, Filter, Record};
+    use bot::policy::Mode;
+
+    #[test]
+    fn skips_invalid_records() {
+        let path = env::temp_dir().join(format!("sonmhub-audit-{}.log", ::std::process::id()));
+        let merge = Record::event("sonm-io/core", 1, "abc", Mode::Live, "ready".into())
+            .with_action(Action::Merge, "merged".into());
+        let comment = Record::event("sonm-io/core", 2, "", Mode::Live, "checklist".into())
+            .with_action(Action::Comment, "ok".into());
+
+        {
+            let mut file = File::create(&path).unwrap();
+            writeln!(file, "{}", ::serde_json::to_string(&merge).unwrap()).unwrap();
+            writeln!(file, "{{\"timestamp\": \"2026-").unwrap();
+            writeln!(file).unwrap();
+            write!(file, "{}", ::serde_json::to_string(&comment).unwrap()).unwrap();
+        }
+
+        let records = query(&path, &Filter::default());
+        let filtered = query(
+            &path,
+            &Filter {
+                action: Some(Action::Comment),
+                ..Filter::default()
+            },
+        );
+        fs::remove_file(&path).unwrap();
+
+        let numbers: Vec<u64> = records.unwrap().iter().map(|v| v.number).collect();
+        assert_eq!(vec![1, 2], numbers);
+        let numbers: Vec<u64> = filtered.unwrap().iter().map(|v| v.number).collect();
+        assert_eq!(vec![2], numbers);
+    }
+}
@@ -0,0 +1,95 @@
+use std::fmt::Write;
+
+use bot::policy::{Decision, Policy, Reason};
+
+/// Hidden marker of the checklist comment, which allows to find it again
+/// after restart.
+pub const MARKER: &str = "<!-- sonmbot:checklist -->";
+
+fn item(result: &mut String, done: bool, text: &str) {
+    let mark = if done { "x" } else { " " };
+    let _ = writeln!(result, "- [{}] {}", mark, text);
+}
+
+/// Appends reasons the condition is not met to its text.
+fn details(text: &str, reasons: &[String]) -> String {
+    let mut result = text.to_owned();
+    for reason in reasons {
+        result.push_str("; ");
+        result.push_str(reason);
+    }
+    result
+}
+
+/// Renders the checklist of merge conditions described in the crate docs.
+///
+/// `position` is the zero-based position of the PR in the merge queue, if
+/// it has been approved with a comment command.
+pub fn render(decision: &Decision, policy: &Policy, position: Option<usize>, login: &str) -> String {
+    let reasons: &[Reason] = match decision {
+        Decision::Merge => &[],
+        Decision::Block(reasons) => reasons,
+    };
+
+    let mut result = String::new();
+    let _ = writeln!(result, "{}", MARKER);
+    let _ = writeln!(result, "**Merge checklist**\n");
+
+    for reason in reasons {
+        if let Reason::Incomplete(..) = reason {
+            item(&mut result, false, &format!("Fully evaluated; {}", reason));
+        }
+    }
+
+    let wip = reasons.contains(&Reason::WorkInProgress);
+    item(&mut result, !wip, &format!("Not a work in progress, i.e. no `{}` label or word in the title", policy.wip()));
+
+    let draft = reasons.contains(&Reason::Draft);
+    item(&mut result, !draft, "Not a draft, i.e. marked as ready for review");
+
+    if policy.checks() {
+        let pending: Vec<String> = reasons
+            .iter()
+            .filter(|v| match v {
+                Reason::ChecksFailed(..) | Reason::ChecksPending(..) => true,
+                _ => false,
+            })
+            .map(|v| v.to_string())
+            .collect();
+        item(&mut result, pending.is_empty(), &details("Status checks have passed", &pending));
+    }
+
+    let missing: Vec<String> = reasons
+        .iter()
+        .filter(|v| match v {
+            Reason::ChangesRequested(..) |
+            Reason::NotEnoughApprovals { .. } |
+            Reason::MissingOwners(..) |
+            Reason::UnresolvableOwners(..) => true,
+            _ => false,
+        })
+        .map(|v| v.to_string())
+        .collect();
+    let owners = if policy.code_owners() { " and code owners" } else { "" };
+    let text = format!("Approved by {} reviewer(s){} with no changes requested", policy.approvals(), owners);
+    item(&mut result, missing.is_empty(), &details(&text, &missing));
+
+    let (done, text) = if reasons.contains(&Reason::Conflicts) {
+        (false, "No merge conflicts; has merge conflicts with the base branch")
+    } else if reasons.contains(&Reason::MergeabilityUnknown) {
+        (false, "No merge conflicts; mergeability is being computed")
+    } else {
+        (true, "No merge conflicts")
+    };
+    item(&mut result, done, text);
+
+    match position {
+        Some(position) => item(&mut result, true, &format!("Queued for merging, #{} in the queue", position + 1)),
+        None if policy.require_command() => {
+            item(&mut result, false, &format!("Queued for merging, comment `@{} r+` to queue", login))
+        }
+        None => {}
+    }
+
+    result
+}
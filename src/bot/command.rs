@@ -0,0 +1,154 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+/// A command given to the bot in a PR comment, like `@sonmbot r+`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    /// `merge` or `r+` approves the PR for merging on behalf of the
+    /// commenter, while `r=user` does so on behalf of the given user.
+    Approve(Option<String>),
+    /// Allows to try merging the PR again after a failed attempt.
+    Retry,
+    /// `cancel` or `r-` removes the PR from the merge queue.
+    Cancel,
+    /// Sets the PR priority in the merge queue, higher goes first.
+    Priority(i32),
+    /// Marks the PR as a rollup, which goes after other PRs of the same
+    /// priority.
+    Rollup,
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "merge" | "r+" => return Ok(Command::Approve(None)),
+            "retry" => return Ok(Command::Retry),
+            "cancel" | "r-" => return Ok(Command::Cancel),
+            "rollup" => return Ok(Command::Rollup),
+            _ => {}
+        }
+
+        if value.starts_with("r=") {
+            let user = value[2..].trim_left_matches('@');
+            if user.is_empty() {
+                return Err("`r=` requires a user".into());
+            }
+            return Ok(Command::Approve(Some(user.into())));
+        }
+
+        if value.starts_with("priority=") {
+            return value[9..]
+                .parse()
+                .map(Command::Priority)
+                .map_err(|_| format!("invalid priority in `{}`", value));
+        }
+
+        Err(format!("unknown command `{}`", value))
+    }
+}
+
+impl Display for Command {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Command::Approve(None) => fmt.write_str("r+"),
+            Command::Approve(Some(user)) => write!(fmt, "r={}", user),
+            Command::Retry => fmt.write_str("retry"),
+            Command::Cancel => fmt.write_str("cancel"),
+            Command::Priority(priority) => write!(fmt, "priority={}", priority),
+            Command::Rollup => fmt.write_str("rollup"),
+        }
+    }
+}
+
+/// Parses commands addressed to the bot with the given login.
+///
+/// Each line starting with the `@login` mention holds whitespace-separated
+/// commands, other lines are ignored. Fails on the first unknown command, so
+/// that a typo does not apply the rest of the line partially.
+pub fn parse(body: &str, login: &str) -> Result<Vec<Command>, String> {
+    let mut commands = Vec::new();
+
+    for line in body.lines() {
+        let mut words = line.split_whitespace();
+        let mention = match words.next() {
+            Some(word) => word.trim_right_matches(|c| c == ':' || c == ','),
+            None => continue,
+        };
+
+        if !mention.starts_with('@') || !mention[1..].eq_ignore_ascii_case(login) {
+            continue;
+        }
+
+        for word in words {
+            commands.push(word.parse()?);
+        }
+    }
+
+    Ok(commands)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, Command};
+
+    #[test]
+    fn parses_commands_after_mention() {
+        let body = "Looks good.\n@sonmbot r+ rollup\n@sonmbot: priority=10\n@sonmbot, retry cancel r-";
+
+        assert_eq!(
+            Ok(vec![
+                Command::Approve(None),
+                Command::Rollup,
+                Command::Priority(10),
+                Command::Retry,
+                Command::Cancel,
+                Command::Cancel,
+            ]),
+            parse(body, "sonmbot")
+        );
+    }
+
+    #[test]
+    fn matches_mention_case_insensitively() {
+        assert_eq!(Ok(vec![Command::Approve(None)]), parse("@SonmBot merge", "sonmbot"));
+    }
+
+    #[test]
+    fn ignores_lines_without_mention() {
+        let body = "r+\nplease @sonmbot r+\n@sonmbotx r+\n@other r+\nsonmbot r+\n\n";
+        assert_eq!(Ok(vec![]), parse(body, "sonmbot"));
+    }
+
+    #[test]
+    fn approves_on_behalf_of_user() {
+        assert_eq!(
+            Ok(vec![Command::Approve(Some("alice".into())), Command::Approve(Some("bob".into()))]),
+            parse("@sonmbot r=alice r=@bob", "sonmbot")
+        );
+        assert!(parse("@sonmbot r=", "sonmbot").is_err());
+        assert!(parse("@sonmbot r=@", "sonmbot").is_err());
+    }
+
+    #[test]
+    fn parses_priorities() {
+        assert_eq!(Ok(vec![Command::Priority(-5)]), parse("@sonmbot priority=-5", "sonmbot"));
+        assert!(parse("@sonmbot priority=high", "sonmbot").is_err());
+        assert!(parse("@sonmbot priority=", "sonmbot").is_err());
+    }
+
+    #[test]
+    fn fails_on_unknown_commands() {
+        assert_eq!(Err("unknown command `r++`".into()), parse("@sonmbot r+ r++", "sonmbot"));
+        // Commands themselves are case-sensitive.
+        assert!(parse("@sonmbot R+", "sonmbot").is_err());
+    }
+
+    #[test]
+    fn displays_commands_as_parsed() {
+        for value in &["r+", "r=alice", "retry", "cancel", "priority=3", "rollup"] {
+            assert_eq!(*value, value.parse::<Command>().unwrap().to_string());
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::fs::{self, DirBuilder, Permissions};
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::path::Path;
+use std::process;
+
+use actix_web::server::{HttpServer, IntoHttpHandler};
+use tokio_uds::UnixListener;
+
+use config::{ListenAddr, ListenerConfig, NetworkConfig};
+use tls::Certificates;
+
+/// Applies server tuning options.
+pub fn tune<H: IntoHttpHandler + 'static>(server: HttpServer<H>, cfg: &NetworkConfig) -> HttpServer<H> {
+    let server = server
+        .backlog(cfg.backlog())
+        .keep_alive(cfg.keep_alive())
+        .client_timeout(cfg.client_timeout().as_secs() * 1000)
+        .shutdown_timeout(cfg.shutdown_timeout());
+
+    match cfg.workers() {
+        Some(workers) => server.workers(workers),
+        None => server,
+    }
+}
+
+/// Binds the server on the listener address and starts it.
+///
+/// Returns certificates of a TLS listener, which must be watched for changes.
+pub fn listen<H>(server: HttpServer<H>, cfg: &ListenerConfig) -> io::Result<Option<Certificates>>
+where
+    H: IntoHttpHandler + 'static,
+{
+    match cfg.addr() {
+        ListenAddr::Tcp { addr, tls: None } => {
+            server.bind(addr)?.start();
+            Ok(None)
+        }
+        ListenAddr::Tcp { addr, tls: Some(tls) } => {
+            let to_io = |err| io::Error::new(io::ErrorKind::Other, err);
+
+            let certificates = Certificates::new(tls.clone()).map_err(to_io)?;
+            let acceptor = certificates.acceptor().map_err(to_io)?;
+            server.bind_ssl(addr, acceptor)?.start();
+            Ok(Some(certificates))
+        }
+        ListenAddr::Unix { path, mode } => {
+            // A socket left by the previous run prevents binding, but never
+            // remove anything else.
+            if let Ok(metadata) = fs::symlink_metadata(path) {
+                if metadata.file_type().is_socket() {
+                    fs::remove_file(path)?;
+                }
+            }
+
+            let listener = bind_unix(path, *mode)?;
+            server.start_incoming(listener.incoming(), false);
+            Ok(None)
+        }
+    }
+}
+
+/// Binds a Unix socket with the given permissions.
+///
+/// The socket is created in a private directory next to the path and moved
+/// into place only after its permissions are set, so that it is never
+/// accessible with the permissions the umask gives.
+fn bind_unix(path: &Path, mode: u32) -> io::Result<UnixListener> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "socket path has no file name"))?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let dir = parent.join(format!(".{}.{}", name.to_string_lossy(), process::id()));
+    DirBuilder::new().mode(0o700).create(&dir)?;
+
+    let tmp = dir.join(name);
+    let result = UnixListener::bind(&tmp).and_then(|listener| {
+        fs::set_permissions(&tmp, Permissions::from_mode(mode))?;
+        fs::rename(&tmp, path)?;
+        Ok(listener)
+    });
+
+    // Either the socket is moved already or binding has failed.
+    let _ = fs::remove_file(&tmp);
+    if let Err(err) = fs::remove_dir(&dir) {
+        warn!("failed to remove `{}`: {}", dir.display(), err);
+    }
+
+    result
+}
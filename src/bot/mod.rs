@@ -0,0 +1,163 @@
+mod checklist;
+mod command;
+mod listener;
+pub mod owners;
+pub mod policy;
+mod queue;
+mod router;
+mod worker;
+
+use actix;
+use actix_web::{http, server, App};
+
+use std::path::PathBuf;
+
+pub use self::router::Router;
+use allowlist::Allowlist;
+use audit::Audit;
+use config::{Config, Overrides, Scope};
+use deliveries::Deliveries;
+use github::Client;
+use health::{self, Health, Version};
+use metrics;
+use futures::prelude::*;
+use futures::sync::mpsc;
+use reload::{self, Reloader};
+use runtime::{Runtime, Status};
+use server::{Event, Server, Shared};
+
+/// Path to the file where all accepted webhook events are appended.
+const EVENT_LOG: &str = "event.log";
+
+/// Path to the file where bot decisions are appended.
+const AUDIT_LOG: &str = "audit.log";
+
+/// Capacity of the queue between the HTTP server and the runtime.
+///
+/// When the queue is full, the server rejects new hooks with `503 Service
+/// Unavailable` instead of waiting for a free slot, so that a stalled runtime
+/// can not exhaust HTTP workers.
+const EVENT_QUEUE_CAPACITY: usize = 1024;
+
+type State = Server<mpsc::Sender<Event>>;
+
+/// Builds an application serving endpoints of the given scopes.
+fn app(state: State, scopes: &[Scope]) -> App<State> {
+    let mut app = App::with_state(state);
+    for scope in scopes {
+        app = match scope {
+            Scope::Hook => app.resource("/hook", |r| r.method(http::Method::POST).a(Server::index)),
+            Scope::Health => app
+                .resource("/healthz", |r| r.method(http::Method::GET).f(health::healthz))
+                .resource("/readyz", |r| r.method(http::Method::GET).f(health::readyz))
+                .resource("/version", |r| r.method(http::Method::GET).f(health::version)),
+            Scope::Metrics => app.resource("/metrics", |r| r.method(http::Method::GET).f(metrics::metrics)),
+            Scope::Admin => app.resource("/admin/reload", |r| r.method(http::Method::POST).a(reload::reload)),
+        };
+    }
+
+    app
+}
+
+/// Runs the bot with the configuration loaded from the given path with the
+/// given overrides, which are used again when the configuration is reloaded.
+pub fn run(cfg: Config, path: PathBuf, overrides: Overrides) -> i32 {
+    let sys = actix::System::new("sonmbot");
+
+    let (tx, rx) = mpsc::channel(EVENT_QUEUE_CAPACITY);
+    let status = Status::new();
+
+    let client = match Client::new(cfg.github().clone()) {
+        Ok(client) => client,
+        Err(err) => {
+            error!("failed to initialize GitHub client: {:?}", err);
+            return 1;
+        }
+    };
+
+    let audit = match Audit::new(AUDIT_LOG) {
+        Ok(audit) => audit,
+        Err(err) => {
+            error!("{}", err);
+            return 1;
+        }
+    };
+
+    let mut router = Router::new(cfg.merge().clone(), client.clone(), audit);
+    router.start();
+
+    let runtime = match Runtime::new(EVENT_LOG, rx, status.clone(), router) {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            error!("failed to initialize runtime: {}", err);
+            return 1;
+        }
+    };
+
+    let deliveries = match Deliveries::new(cfg.deliveries()) {
+        Ok(deliveries) => deliveries,
+        Err(err) => {
+            error!("failed to restore webhook deliveries: {}", err);
+            return 1;
+        }
+    };
+
+    let health = Health::new(status.clone(), EVENT_QUEUE_CAPACITY, Version::new(cfg.hash()));
+    let reloader = Reloader::new(path, overrides, cfg.clone(), tx.clone(), health.clone());
+
+    let allowlist = Allowlist::new(cfg.hook().allowlist().clone());
+
+    let shared = Shared {
+        status,
+        deliveries,
+        health: health.clone(),
+        reloader: reloader.clone(),
+        allowlist: allowlist.clone(),
+    };
+    let state = Server::new(tx, cfg.hook().secret().clone(), cfg.network().max_payload(), shared);
+
+    let listeners = cfg.network().listeners();
+    if listeners.is_empty() {
+        error!("neither `network.addr` nor `network.listeners` is specified");
+        return 1;
+    }
+
+    // Each listener has its own server, because they serve different
+    // endpoints.
+    for listener in listeners {
+        let server = {
+            let state = state.clone();
+            let scopes = listener.scopes().to_vec();
+            server::new(move || app(state.clone(), &scopes))
+        };
+        let server = listener::tune(server, cfg.network());
+
+        match listener::listen(server, &listener) {
+            Ok(certificates) => {
+                if let Some(certificates) = certificates {
+                    sys.handle().spawn(certificates.watch());
+                }
+            }
+            Err(err) => {
+                error!("failed to listen on {}: {}", listener.addr(), err);
+                return 1;
+            }
+        }
+
+        info!("listening on {} for {:?}", listener.addr(), listener.scopes());
+    }
+
+    sys.handle().spawn(runtime.run().then(|result| {
+        match result {
+            Ok(()) => info!("runtime finished"),
+            Err(err) => error!("finished runtime with {}", err),
+        }
+        Ok(())
+    }));
+
+    sys.handle().spawn(allowlist.watch(client.clone()));
+    sys.handle().spawn(health.watch(client));
+    reloader.listen();
+
+    sys.run()
+}
@@ -0,0 +1,273 @@
+//! `CODEOWNERS` parsing and matching.
+//!
+//! Patterns follow the `.gitignore` rules GitHub uses: a pattern without a
+//! slash, other than a trailing one, matches at any depth, `*` and `?` do
+//! not cross directories, `**` does, and a pattern matching a directory
+//! covers everything inside it. Unlike `.gitignore`, a directory matched
+//! by a wildcard, like `docs/*`, does not cover nested files, unless the
+//! pattern ends with a slash. The last matching rule wins.
+
+/// Locations GitHub looks for the file at, in order.
+pub const LOCATIONS: &[&str] = &[".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+/// Single `CODEOWNERS` line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    pattern: String,
+    /// Either `@user`, `@org/team` or an email, as written.
+    owners: Vec<String>,
+}
+
+impl Rule {
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn owners(&self) -> &[String] {
+        &self.owners
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        let trimmed = self.pattern.trim_right_matches('/');
+        let directory = trimmed.len() != self.pattern.len();
+        let anchored = trimmed.starts_with('/') || trimmed.contains('/');
+
+        let mut pattern: Vec<&str> = trimmed.trim_left_matches('/').split('/').collect();
+        if !anchored {
+            pattern.insert(0, "**");
+        }
+        let path: Vec<&str> = path.split('/').collect();
+
+        matches_path(&pattern, &path, directory, false)
+    }
+}
+
+/// Matches path segments, letting the pattern match any parent directory,
+/// which is either named literally, ends the pattern with a slash or is
+/// matched by a trailing `**`.
+fn matches_path(pattern: &[&str], path: &[&str], directory: bool, literal: bool) -> bool {
+    if pattern.is_empty() {
+        return if path.is_empty() { !directory } else { directory || literal };
+    }
+
+    if pattern[0] == "**" {
+        return (0..path.len() + 1).any(|id| matches_path(&pattern[1..], &path[id..], directory, true));
+    }
+
+    let literal = !pattern[0].contains(|c| c == '*' || c == '?');
+    !path.is_empty() && matches_segment(pattern[0].as_bytes(), path[0].as_bytes()) &&
+        matches_path(&pattern[1..], &path[1..], directory, literal)
+}
+
+fn matches_segment(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..name.len() + 1).any(|id| matches_segment(rest, &name[id..])),
+        Some((b'?', rest)) => !name.is_empty() && matches_segment(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && matches_segment(rest, &name[1..]),
+    }
+}
+
+/// Parsed `CODEOWNERS` file.
+#[derive(Clone, Debug, Default)]
+pub struct CodeOwners {
+    rules: Vec<Rule>,
+}
+
+impl CodeOwners {
+    /// Parses the file, skipping comments and blank lines.
+    pub fn parse(text: &str) -> Self {
+        let rules = text
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or("").trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                let mut words = line.split_whitespace();
+                let pattern = words.next().unwrap_or("").to_owned();
+                Rule {
+                    pattern,
+                    owners: words.map(|v| v.to_owned()).collect(),
+                }
+            })
+            .collect();
+
+        Self { rules }
+    }
+
+    /// Returns the rule owning the path, if any.
+    pub fn owner(&self, path: &str) -> Option<&Rule> {
+        self.rules.iter().rev().find(|rule| rule.matches(path))
+    }
+
+    /// Returns distinct rules owning the given paths, skipping the ones
+    /// that explicitly leave paths without owners.
+    pub fn covering<'a, I>(&self, paths: I) -> Vec<&Rule>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut result: Vec<&Rule> = Vec::new();
+        for path in paths {
+            if let Some(rule) = self.owner(path) {
+                if !rule.owners.is_empty() && !result.iter().any(|v| *v == rule) {
+                    result.push(rule);
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Approval requirement of a single matched pattern, which is satisfied by
+/// an approval from any of its owners.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Requirement {
+    rule: Rule,
+    /// Logins of the owners, with teams expanded to their members.
+    logins: Vec<String>,
+}
+
+impl Requirement {
+    pub fn new(rule: Rule, logins: Vec<String>) -> Self {
+        Self { rule, logins }
+    }
+
+    pub fn rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    pub fn logins(&self) -> &[String] {
+        &self.logins
+    }
+
+    /// Returns `true` if any of the given users is an owner.
+    pub fn is_satisfied_by<'a, I>(&self, users: I) -> bool
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        users
+            .into_iter()
+            .any(|user| self.logins.iter().any(|v| v.eq_ignore_ascii_case(user)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CodeOwners, Requirement};
+
+    /// Example from the GitHub documentation on code owners.
+    const EXAMPLE: &str = "
+# These owners will be the default owners for everything in the repo.
+*       @global-owner1 @global-owner2
+
+*.js    @js-owner #This is an inline comment.
+*.go docs@example.com
+*.txt @octo-org/octocats
+/build/logs/ @doctocat
+docs/*  docs@example.com
+apps/ @octocat
+/docs/ @doctocat
+/scripts/ @doctocat @octocat
+**/logs @octocat
+
+# Nobody owns this subdirectory.
+/apps/ @octocat
+/apps/github
+";
+
+    fn owner<'a>(codeowners: &'a CodeOwners, path: &str) -> Option<&'a str> {
+        codeowners.owner(path).map(|v| v.pattern())
+    }
+
+    #[test]
+    fn matches_documented_example() {
+        let codeowners = CodeOwners::parse(EXAMPLE);
+        let cases = [
+            ("README.md", Some("*")),
+            ("src/index.js", Some("*.js")),
+            ("main.go", Some("*.go")),
+            ("cmd/server/main.go", Some("*.go")),
+            ("notes/todo.txt", Some("*.txt")),
+            ("build/logs/build.log", Some("**/logs")),
+            ("deeply/nested/logs/debug.log", Some("**/logs")),
+            ("docs/getting-started.md", Some("/docs/")),
+            ("docs/build-app/troubleshooting.md", Some("/docs/")),
+            ("src/docs/index.md", Some("*")),
+            ("src/docs/build-app/troubleshooting.md", Some("*")),
+            ("apps/web/index.html", Some("/apps/")),
+            ("frontend/apps/index.html", Some("apps/")),
+            ("apps/github/index.js", Some("/apps/github")),
+            ("scripts/deploy.sh", Some("/scripts/")),
+            ("src/scripts/deploy.sh", Some("*")),
+        ];
+
+        for &(path, expected) in &cases {
+            assert_eq!(expected, owner(&codeowners, path), "{}", path);
+        }
+
+        let rule = codeowners.owner("docs/index.md").unwrap();
+        assert_eq!(&["@doctocat".to_owned()][..], rule.owners());
+        assert_eq!(&["@js-owner".to_owned()][..], codeowners.owner("a.js").unwrap().owners());
+    }
+
+    #[test]
+    fn anchors_patterns() {
+        let codeowners = CodeOwners::parse("/root.md @a\nsrc/lib.rs @b\nlib.rs @c\n");
+        let cases = [
+            ("root.md", Some("/root.md")),
+            ("nested/root.md", None),
+            ("src/lib.rs", Some("lib.rs")),
+            ("other/src/lib.rs", Some("lib.rs")),
+            ("other/main.rs", None),
+        ];
+
+        for &(path, expected) in &cases {
+            assert_eq!(expected, owner(&codeowners, path), "{}", path);
+        }
+
+        let codeowners = CodeOwners::parse("src/lib.rs @b\n");
+        assert_eq!(None, owner(&codeowners, "other/src/lib.rs"));
+    }
+
+    #[test]
+    fn matches_wildcards() {
+        let codeowners = CodeOwners::parse("docs/*.md @a\nsrc/**/test/ @b\nv?.rs @c\nbuild-*/ @d\n");
+        let cases = [
+            ("docs/index.md", Some("docs/*.md")),
+            ("docs/api/index.md", None),
+            ("src/test/a.rs", Some("src/**/test/")),
+            ("src/a/b/test/c/d.rs", Some("src/**/test/")),
+            ("src/test", None),
+            ("v1.rs", Some("v?.rs")),
+            ("v10.rs", None),
+            ("build-linux/out/bin", Some("build-*/")),
+        ];
+
+        for &(path, expected) in &cases {
+            assert_eq!(expected, owner(&codeowners, path), "{}", path);
+        }
+    }
+
+    #[test]
+    fn covers_paths_with_distinct_owned_rules() {
+        let codeowners = CodeOwners::parse(EXAMPLE);
+        let patterns: Vec<&str> = codeowners
+            .covering(vec!["a.js", "b.js", "apps/github/c.js", "README.md"])
+            .iter()
+            .map(|v| v.pattern())
+            .collect();
+
+        assert_eq!(vec!["*.js", "*"], patterns);
+    }
+
+    #[test]
+    fn requirement_is_satisfied_by_any_owner() {
+        let codeowners = CodeOwners::parse("* @Alice @org/team\n");
+        let rule = codeowners.owner("a").unwrap().clone();
+        let requirement = Requirement::new(rule, vec!["Alice".into(), "bob".into()]);
+
+        assert!(requirement.is_satisfied_by(vec!["alice"]));
+        assert!(requirement.is_satisfied_by(vec!["carol", "BOB"]));
+        assert!(!requirement.is_satisfied_by(vec!["carol"]));
+        assert!(!requirement.is_satisfied_by(Vec::new()));
+    }
+}
@@ -0,0 +1,791 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+use bot::owners::Requirement;
+use github::{MergeableState, PullRequest, Review, ReviewState, StatusState};
+
+/// Review author associations, whose reviews are taken into account.
+const TRUSTED_ASSOCIATIONS: &[&str] = &["OWNER", "MEMBER", "COLLABORATOR"];
+
+fn default_approvals() -> u32 {
+    1
+}
+
+fn default_wip() -> String {
+    "wip".into()
+}
+
+fn default_checks() -> bool {
+    true
+}
+
+/// How the bot acts on its decisions.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    Live,
+    /// Only logs what would be done, without any mutating GitHub calls.
+    DryRun,
+    /// Like `dry_run`, but also comments on PRs what would be done, which
+    /// is the only mutating call made.
+    Shadow,
+}
+
+impl Default for Mode {
+    fn default() -> Self {
+        Mode::Live
+    }
+}
+
+fn default_reviewers() -> u32 {
+    1
+}
+
+/// How reviewers are picked for new PRs.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    /// An owner of each `CODEOWNERS` pattern matching changed files.
+    CodeOwners,
+    /// Members of the team in turn.
+    RoundRobin,
+    /// Members of the team with the fewest pending review requests.
+    LoadBalance,
+}
+
+impl Default for Strategy {
+    fn default() -> Self {
+        Strategy::CodeOwners
+    }
+}
+
+/// Automatic review requests of PRs, made when they are opened or marked
+/// ready for review.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Reviewers {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default)]
+    strategy: Strategy,
+    /// Team to pick reviewers from, like `org/team`, required unless the
+    /// strategy is `code_owners`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    team: Option<String>,
+    /// Number of reviewers to request from the team, including the ones
+    /// already requested.
+    #[serde(default = "default_reviewers")]
+    count: u32,
+    /// Users that are away, who are never requested.
+    #[serde(default)]
+    away: Vec<String>,
+}
+
+impl Reviewers {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn strategy(&self) -> Strategy {
+        self.strategy
+    }
+
+    /// Returns the team organization and slug.
+    pub fn team(&self) -> Option<(&str, &str)> {
+        let team = self.team.as_ref()?;
+        let id = team.find('/')?;
+        let (org, slug) = (&team[..id], &team[id + 1..]);
+        if org.is_empty() || slug.is_empty() {
+            return None;
+        }
+        Some((org, slug))
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn is_away(&self, user: &str) -> bool {
+        self.away.iter().any(|v| v.eq_ignore_ascii_case(user))
+    }
+}
+
+/// Rules that must be satisfied for a PR to be merged.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Policy {
+    /// Number of approvals required.
+    #[serde(default = "default_approvals")]
+    approvals: u32,
+    /// Label that marks a PR as a work in progress. The same word in the
+    /// title has the same effect.
+    #[serde(default = "default_wip")]
+    wip: String,
+    /// Whether all status checks must pass.
+    #[serde(default = "default_checks")]
+    checks: bool,
+    /// Whether PRs are merged only after being approved with a comment
+    /// command, like `@sonmbot r+`. Otherwise every ready PR is merged, while
+    /// commands only put PRs ahead of others.
+    #[serde(default)]
+    require_command: bool,
+    /// Whether to merge the base branch into the branch of the next PR to
+    /// merge, when it is behind.
+    #[serde(default)]
+    update: bool,
+    #[serde(default)]
+    mode: Mode,
+    /// Whether only approvals of the current head count, so that pushing new
+    /// commits requires approving again.
+    #[serde(default)]
+    fresh_approvals: bool,
+    /// Whether to dismiss stale approvals on GitHub, which makes sense only
+    /// along with `fresh_approvals`.
+    #[serde(default)]
+    dismiss_stale: bool,
+    /// Whether to require an approval from an owner of each `CODEOWNERS`
+    /// pattern matching files changed by the PR.
+    #[serde(default)]
+    code_owners: bool,
+    #[serde(default)]
+    reviewers: Reviewers,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            approvals: default_approvals(),
+            wip: default_wip(),
+            checks: default_checks(),
+            require_command: false,
+            update: false,
+            mode: Mode::Live,
+            fresh_approvals: false,
+            dismiss_stale: false,
+            code_owners: false,
+            reviewers: Reviewers::default(),
+        }
+    }
+}
+
+/// Per-repository overrides of the default policy.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct PolicyOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    approvals: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    wip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    checks: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    require_command: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    update: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mode: Option<Mode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fresh_approvals: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dismiss_stale: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code_owners: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reviewers: Option<Reviewers>,
+}
+
+impl PolicyOverrides {
+    /// Returns the given policy with these overrides applied.
+    pub fn apply(&self, policy: &Policy) -> Policy {
+        Policy {
+            approvals: self.approvals.unwrap_or(policy.approvals),
+            wip: self.wip.clone().unwrap_or_else(|| policy.wip.clone()),
+            checks: self.checks.unwrap_or(policy.checks),
+            require_command: self.require_command.unwrap_or(policy.require_command),
+            update: self.update.unwrap_or(policy.update),
+            mode: self.mode.unwrap_or(policy.mode),
+            fresh_approvals: self.fresh_approvals.unwrap_or(policy.fresh_approvals),
+            dismiss_stale: self.dismiss_stale.unwrap_or(policy.dismiss_stale),
+            code_owners: self.code_owners.unwrap_or(policy.code_owners),
+            reviewers: self.reviewers.clone().unwrap_or_else(|| policy.reviewers.clone()),
+        }
+    }
+}
+
+/// A reason that prevents a PR from being merged.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Reason {
+    WorkInProgress,
+    /// The PR is a draft, which GitHub refuses to merge.
+    Draft,
+    Conflicts,
+    /// GitHub has not computed mergeability yet.
+    MergeabilityUnknown,
+    ChecksFailed(Vec<String>),
+    ChecksPending(Vec<String>),
+    ChangesRequested(Vec<String>),
+    NotEnoughApprovals { required: u32, actual: u32 },
+    /// The PR has more labels, checks or reviews than fetched, so it can
+    /// not be evaluated reliably.
+    Incomplete(Vec<String>),
+    /// Owners of `CODEOWNERS` patterns without an approval, each listed as
+    /// alternatives, like `@alice or @org/team`.
+    MissingOwners(Vec<String>),
+    /// `CODEOWNERS` patterns none of whose owners may approve, like emails
+    /// or teams that can not be resolved, so the file must be fixed.
+    UnresolvableOwners(Vec<String>),
+}
+
+impl Reason {
+    /// Returns `true` if the reason can not go away without an action from
+    /// the PR author or reviewers, as opposed to just waiting.
+    pub fn is_failure(&self) -> bool {
+        match self {
+            Reason::WorkInProgress |
+            Reason::Draft |
+            Reason::Conflicts |
+            Reason::ChecksFailed(..) |
+            Reason::ChangesRequested(..) |
+            Reason::Incomplete(..) |
+            Reason::UnresolvableOwners(..) => true,
+            Reason::MergeabilityUnknown |
+            Reason::ChecksPending(..) |
+            Reason::NotEnoughApprovals { .. } |
+            Reason::MissingOwners(..) => false,
+        }
+    }
+}
+
+impl Display for Reason {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Reason::WorkInProgress => fmt.write_str("work in progress"),
+            Reason::Draft => fmt.write_str("is a draft"),
+            Reason::Conflicts => fmt.write_str("has merge conflicts"),
+            Reason::MergeabilityUnknown => fmt.write_str("mergeability is being computed"),
+            Reason::ChecksFailed(checks) => write!(fmt, "failed checks: {}", checks.join(", ")),
+            Reason::ChecksPending(checks) => write!(fmt, "pending checks: {}", checks.join(", ")),
+            Reason::ChangesRequested(users) => {
+                write!(fmt, "changes requested by {}", users.join(", "))
+            }
+            Reason::NotEnoughApprovals { required, actual } => {
+                let count = required - actual;
+                let suffix = if count == 1 { "" } else { "s" };
+                write!(fmt, "needs {} more approval{}", count, suffix)
+            }
+            Reason::Incomplete(what) => write!(fmt, "too many {} to evaluate", what.join(", ")),
+            Reason::MissingOwners(owners) => write!(fmt, "needs approval of {}", owners.join(" and ")),
+            Reason::UnresolvableOwners(patterns) => {
+                write!(fmt, "no code owners of {} can approve", patterns.join(", "))
+            }
+        }
+    }
+}
+
+/// Policy verdict for a single PR.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Decision {
+    Merge,
+    Block(Vec<Reason>),
+}
+
+impl Decision {
+    pub fn is_merge(&self) -> bool {
+        *self == Decision::Merge
+    }
+}
+
+impl Display for Decision {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Decision::Merge => fmt.write_str("ready to merge"),
+            Decision::Block(reasons) => {
+                for (id, reason) in reasons.iter().enumerate() {
+                    if id > 0 {
+                        fmt.write_str("; ")?;
+                    }
+                    reason.fmt(fmt)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Policy {
+    pub fn approvals(&self) -> u32 {
+        self.approvals
+    }
+
+    pub fn wip(&self) -> &str {
+        &self.wip
+    }
+
+    pub fn checks(&self) -> bool {
+        self.checks
+    }
+
+    pub fn require_command(&self) -> bool {
+        self.require_command
+    }
+
+    pub fn update(&self) -> bool {
+        self.update
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn fresh_approvals(&self) -> bool {
+        self.fresh_approvals
+    }
+
+    pub fn dismiss_stale(&self) -> bool {
+        self.dismiss_stale
+    }
+
+    pub fn code_owners(&self) -> bool {
+        self.code_owners
+    }
+
+    pub fn reviewers(&self) -> &Reviewers {
+        &self.reviewers
+    }
+
+    /// Returns approvals of trusted users given to commits other than the
+    /// current head, i.e. the ones that stop counting with `fresh_approvals`.
+    pub fn stale_approvals<'a>(&self, pr: &'a PullRequest) -> Vec<&'a Review> {
+        pr.reviews()
+            .iter()
+            .filter(|v| TRUSTED_ASSOCIATIONS.contains(&v.author_association.as_str()))
+            .filter(|v| v.state == ReviewState::Approved)
+            .filter(|v| v.commit.as_ref().map_or(true, |v| v.oid != pr.head_ref_oid))
+            .collect()
+    }
+
+    fn is_wip(&self, pr: &PullRequest) -> bool {
+        let wip = &self.wip;
+
+        pr.labels().any(|label| label.eq_ignore_ascii_case(wip)) ||
+            pr.title
+                .split(|c: char| !c.is_alphanumeric())
+                .any(|word| word.eq_ignore_ascii_case(wip))
+    }
+
+    /// Evaluates the PR against the rules described in the crate docs.
+    ///
+    /// The status `context` published by the bot itself is not a check, so
+    /// it is ignored. `owners` are the `CODEOWNERS` requirements of the PR,
+    /// which are checked only if enabled, or `None` if changed files are not
+    /// listed in full, so the requirements are unknown.
+    pub fn evaluate(&self, pr: &PullRequest, context: &str, owners: Option<&[Requirement]>) -> Decision {
+        let mut reasons = Vec::new();
+
+        let mut truncated: Vec<String> = pr.truncated().iter().map(|v| v.to_string()).collect();
+        if self.code_owners && owners.is_none() {
+            truncated.push("files".into());
+        }
+        if !truncated.is_empty() {
+            reasons.push(Reason::Incomplete(truncated));
+        }
+
+        if self.is_wip(pr) {
+            reasons.push(Reason::WorkInProgress);
+        }
+
+        if pr.is_draft || pr.mergeable == MergeableState::Draft {
+            reasons.push(Reason::Draft);
+        }
+
+        // Other states are up to GitHub branch protection, which is reported
+        // when merging.
+        if pr.mergeable.is_conflicting() {
+            reasons.push(Reason::Conflicts);
+        } else if pr.mergeable == MergeableState::Unknown {
+            reasons.push(Reason::MergeabilityUnknown);
+        }
+
+        if self.checks {
+            let (mut failed, mut pending) = (Vec::new(), Vec::new());
+            for status in pr.statuses().iter().filter(|v| v.context != context) {
+                match status.state {
+                    StatusState::Success => {}
+                    StatusState::Pending | StatusState::Expected => pending.push(status.context.clone()),
+                    StatusState::Failure | StatusState::Error => failed.push(status.context.clone()),
+                }
+            }
+
+            if !failed.is_empty() {
+                reasons.push(Reason::ChecksFailed(failed));
+            }
+            if !pending.is_empty() {
+                reasons.push(Reason::ChecksPending(pending));
+            }
+        }
+
+        // Only the latest review of each trusted author counts. Comments
+        // neither approve nor reject.
+        let mut latest = HashMap::new();
+        for review in pr.reviews() {
+            if !TRUSTED_ASSOCIATIONS.contains(&review.author_association.as_str()) {
+                continue;
+            }
+
+            // Stale approvals are replaced with nothing, so they also revoke
+            // earlier change requests, while stale change requests still hold
+            // until addressed.
+            let stale = review.commit.as_ref().map_or(true, |v| v.oid != pr.head_ref_oid);
+            if self.fresh_approvals && stale && review.state == ReviewState::Approved {
+                if let Some(author) = review.author.as_ref() {
+                    latest.remove(author.login.as_str());
+                }
+                continue;
+            }
+
+            if let Some(author) = review.author.as_ref() {
+                match review.state {
+                    ReviewState::Approved | ReviewState::ChangesRequested | ReviewState::Dismissed => {
+                        latest.insert(author.login.as_str(), review.state);
+                    }
+                    ReviewState::Commented | ReviewState::Pending => {}
+                }
+            }
+        }
+
+        let mut changes_requested: Vec<String> = latest
+            .iter()
+            .filter(|&(_, state)| *state == ReviewState::ChangesRequested)
+            .map(|(login, _)| login.to_string())
+            .collect();
+        changes_requested.sort();
+
+        if !changes_requested.is_empty() {
+            reasons.push(Reason::ChangesRequested(changes_requested));
+        }
+
+        let approvals = latest.values().filter(|&state| *state == ReviewState::Approved).count() as u32;
+        if approvals < self.approvals {
+            reasons.push(Reason::NotEnoughApprovals {
+                required: self.approvals,
+                actual: approvals,
+            });
+        }
+
+        if self.code_owners {
+            let owners = owners.unwrap_or(&[]);
+            let approvers: Vec<&str> = latest
+                .iter()
+                .filter(|&(_, state)| *state == ReviewState::Approved)
+                .map(|(login, _)| *login)
+                .collect();
+
+            let (resolvable, unresolvable): (Vec<&Requirement>, Vec<&Requirement>) =
+                owners.iter().partition(|v| !v.logins().is_empty());
+
+            let missing: Vec<String> = resolvable
+                .iter()
+                .filter(|v| !v.is_satisfied_by(approvers.iter().cloned()))
+                .map(|v| v.rule().owners().join(" or "))
+                .collect();
+
+            if !missing.is_empty() {
+                reasons.push(Reason::MissingOwners(missing));
+            }
+
+            let unresolvable: Vec<String> = unresolvable
+                .iter()
+                .map(|v| format!("`{}`", v.rule().pattern()))
+                .collect();
+
+            if !unresolvable.is_empty() {
+                reasons.push(Reason::UnresolvableOwners(unresolvable));
+            }
+        }
+
+        if reasons.is_empty() {
+            Decision::Merge
+        } else {
+            Decision::Block(reasons)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::{self, Value};
+
+    use bot::owners::{CodeOwners, Requirement};
+    use github::PullRequest;
+
+    use super::{Decision, Policy, Reason};
+
+    const HEAD: &str = "c0ffee";
+    const CONTEXT: &str = "sonmbot/merge";
+
+    fn review(login: &str, association: &str, state: &str, commit: &str) -> Value {
+        json!({
+            "databaseId": 1,
+            "author": {"login": login},
+            "authorAssociation": association,
+            "state": state,
+            "commit": {"oid": commit},
+        })
+    }
+
+    fn status(context: &str, state: &str) -> Value {
+        json!({"__typename": "StatusContext", "context": context, "state": state})
+    }
+
+    fn check_run(name: &str, status: &str, conclusion: Option<&str>) -> Value {
+        json!({"__typename": "CheckRun", "name": name, "status": status, "conclusion": conclusion})
+    }
+
+    /// Returns a PR ready to merge under the default policy.
+    fn fixture() -> Value {
+        json!({
+            "number": 1,
+            "title": "Add feature",
+            "url": "https://github.com/sonm-io/core/pull/1",
+            "mergeable": "MERGEABLE",
+            "baseRefName": "master",
+            "headRefName": "feature",
+            "headRefOid": HEAD,
+            "isCrossRepository": false,
+            "isDraft": false,
+            "labels": {"nodes": []},
+            "commits": {"nodes": [{"commit": {
+                "oid": HEAD,
+                "statusCheckRollup": {"contexts": {"nodes": [status("ci/travis", "SUCCESS")]}},
+            }}]},
+            "reviewRequests": {"nodes": []},
+            "reviews": {"nodes": [review("alice", "MEMBER", "APPROVED", HEAD)]},
+        })
+    }
+
+    fn set_checks(pr: &mut Value, checks: Vec<Value>) {
+        pr["commits"]["nodes"][0]["commit"]["statusCheckRollup"]["contexts"]["nodes"] = Value::Array(checks);
+    }
+
+    fn evaluate(policy: &Policy, pr: Value) -> Vec<Reason> {
+        evaluate_owners(policy, pr, Some(&[][..]))
+    }
+
+    fn evaluate_owners(policy: &Policy, pr: Value, owners: Option<&[Requirement]>) -> Vec<Reason> {
+        let pr: PullRequest = serde_json::from_value(pr).unwrap();
+        match policy.evaluate(&pr, CONTEXT, owners) {
+            Decision::Merge => Vec::new(),
+            Decision::Block(reasons) => reasons,
+        }
+    }
+
+    #[test]
+    fn merges_ready_pr() {
+        assert_eq!(Vec::<Reason>::new(), evaluate(&Policy::default(), fixture()));
+    }
+
+    #[test]
+    fn blocks_wip_label() {
+        let mut pr = fixture();
+        pr["labels"]["nodes"] = json!([{"name": "WIP"}]);
+
+        assert_eq!(vec![Reason::WorkInProgress], evaluate(&Policy::default(), pr));
+    }
+
+    #[test]
+    fn blocks_wip_word_in_title() {
+        let mut pr = fixture();
+        pr["title"] = json!("[wip] Add feature");
+        assert_eq!(vec![Reason::WorkInProgress], evaluate(&Policy::default(), pr));
+
+        let mut pr = fixture();
+        pr["title"] = json!("Wipe stale caches");
+        assert_eq!(Vec::<Reason>::new(), evaluate(&Policy::default(), pr));
+    }
+
+    #[test]
+    fn blocks_drafts() {
+        let mut pr = fixture();
+        pr["isDraft"] = json!(true);
+        assert_eq!(vec![Reason::Draft], evaluate(&Policy::default(), pr));
+
+        let mut pr = fixture();
+        pr["mergeable"] = json!("draft");
+        assert_eq!(vec![Reason::Draft], evaluate(&Policy::default(), pr));
+    }
+
+    #[test]
+    fn blocks_conflicts_and_unknown_mergeability() {
+        let mut pr = fixture();
+        pr["mergeable"] = json!("CONFLICTING");
+        assert_eq!(vec![Reason::Conflicts], evaluate(&Policy::default(), pr));
+
+        let mut pr = fixture();
+        pr["mergeable"] = json!("UNKNOWN");
+        assert_eq!(vec![Reason::MergeabilityUnknown], evaluate(&Policy::default(), pr));
+    }
+
+    #[test]
+    fn blocks_failed_and_pending_statuses() {
+        let mut pr = fixture();
+        set_checks(&mut pr, vec![
+            status("ci/travis", "FAILURE"),
+            status("ci/circle", "ERROR"),
+            status("ci/jenkins", "PENDING"),
+            status(CONTEXT, "PENDING"),
+        ]);
+
+        assert_eq!(
+            vec![
+                Reason::ChecksFailed(vec!["ci/travis".into(), "ci/circle".into()]),
+                Reason::ChecksPending(vec!["ci/jenkins".into()]),
+            ],
+            evaluate(&Policy::default(), pr)
+        );
+    }
+
+    #[test]
+    fn blocks_failed_and_pending_check_runs() {
+        let mut pr = fixture();
+        set_checks(&mut pr, vec![
+            check_run("test", "COMPLETED", Some("FAILURE")),
+            check_run("lint", "COMPLETED", Some("TIMED_OUT")),
+            check_run("build", "IN_PROGRESS", None),
+            check_run("docs", "COMPLETED", Some("SKIPPED")),
+        ]);
+
+        assert_eq!(
+            vec![
+                Reason::ChecksFailed(vec!["test".into(), "lint".into()]),
+                Reason::ChecksPending(vec!["build".into()]),
+            ],
+            evaluate(&Policy::default(), pr)
+        );
+    }
+
+    #[test]
+    fn counts_latest_review_of_each_author() {
+        let mut pr = fixture();
+        pr["reviews"]["nodes"] = json!([
+            review("alice", "MEMBER", "CHANGES_REQUESTED", HEAD),
+            review("alice", "MEMBER", "COMMENTED", HEAD),
+            review("alice", "MEMBER", "APPROVED", HEAD),
+        ]);
+        assert_eq!(Vec::<Reason>::new(), evaluate(&Policy::default(), pr));
+
+        let mut pr = fixture();
+        pr["reviews"]["nodes"] = json!([
+            review("alice", "MEMBER", "APPROVED", HEAD),
+            review("alice", "MEMBER", "CHANGES_REQUESTED", HEAD),
+        ]);
+        assert_eq!(
+            vec![
+                Reason::ChangesRequested(vec!["alice".into()]),
+                Reason::NotEnoughApprovals { required: 1, actual: 0 },
+            ],
+            evaluate(&Policy::default(), pr)
+        );
+    }
+
+    #[test]
+    fn stale_approval_replaces_change_request() {
+        let mut policy = Policy::default();
+        policy.fresh_approvals = true;
+
+        let mut pr = fixture();
+        pr["reviews"]["nodes"] = json!([
+            review("alice", "MEMBER", "CHANGES_REQUESTED", "a"),
+            review("alice", "MEMBER", "APPROVED", "b"),
+        ]);
+        assert_eq!(
+            vec![Reason::NotEnoughApprovals { required: 1, actual: 0 }],
+            evaluate(&policy, pr.clone())
+        );
+
+        // Without fresh approvals required, the approval still counts.
+        assert_eq!(Vec::<Reason>::new(), evaluate(&Policy::default(), pr));
+    }
+
+    #[test]
+    fn stale_change_request_holds() {
+        let mut policy = Policy::default();
+        policy.fresh_approvals = true;
+
+        let mut pr = fixture();
+        pr["reviews"]["nodes"] = json!([
+            review("alice", "MEMBER", "APPROVED", HEAD),
+            review("bob", "MEMBER", "CHANGES_REQUESTED", "a"),
+        ]);
+        assert_eq!(vec![Reason::ChangesRequested(vec!["bob".into()])], evaluate(&policy, pr));
+    }
+
+    #[test]
+    fn stale_approvals_of_trusted_users_only() {
+        let mut pr = fixture();
+        pr["reviews"]["nodes"] = json!([
+            review("alice", "MEMBER", "APPROVED", "a"),
+            review("mallory", "NONE", "APPROVED", "a"),
+            review("bob", "MEMBER", "APPROVED", HEAD),
+        ]);
+
+        let pr: PullRequest = serde_json::from_value(pr).unwrap();
+        let stale: Vec<&str> = Policy::default()
+            .stale_approvals(&pr)
+            .iter()
+            .filter_map(|v| v.author.as_ref())
+            .map(|v| v.login.as_str())
+            .collect();
+        assert_eq!(vec!["alice"], stale);
+    }
+
+    #[test]
+    fn requires_code_owners() {
+        let mut policy = Policy::default();
+        policy.code_owners = true;
+
+        let codeowners = CodeOwners::parse("*.rs @alice @org/rust\ndocs/ docs@example.com\n*.md @bob\n");
+        let rule = |path: &str| codeowners.owner(path).unwrap().clone();
+        let owners = vec![
+            Requirement::new(rule("src/lib.rs"), vec!["alice".into(), "carol".into()]),
+            Requirement::new(rule("README.md"), vec!["bob".into()]),
+            Requirement::new(rule("docs/index.html"), vec![]),
+        ];
+
+        assert_eq!(
+            vec![
+                Reason::MissingOwners(vec!["@bob".into()]),
+                Reason::UnresolvableOwners(vec!["`docs/`".into()]),
+            ],
+            evaluate_owners(&policy, fixture(), Some(&owners[..]))
+        );
+        assert!(Reason::UnresolvableOwners(vec![]).is_failure());
+    }
+
+    #[test]
+    fn blocks_unknown_code_owners() {
+        let mut policy = Policy::default();
+        policy.code_owners = true;
+
+        assert_eq!(vec![Reason::Incomplete(vec!["files".into()])], evaluate_owners(&policy, fixture(), None));
+        assert_eq!(Vec::<Reason>::new(), evaluate_owners(&Policy::default(), fixture(), None));
+    }
+
+    #[test]
+    fn ignores_untrusted_reviews() {
+        let mut pr = fixture();
+        pr["reviews"]["nodes"] = json!([
+            review("mallory", "CONTRIBUTOR", "APPROVED", HEAD),
+            review("eve", "NONE", "CHANGES_REQUESTED", HEAD),
+        ]);
+
+        assert_eq!(
+            vec![Reason::NotEnoughApprovals { required: 1, actual: 0 }],
+            evaluate(&Policy::default(), pr)
+        );
+    }
+
+    #[test]
+    fn blocks_truncated_reviews() {
+        let mut pr = fixture();
+        pr["reviews"]["pageInfo"] = json!({"hasNextPage": true, "endCursor": "Y3Vyc29y"});
+
+        assert_eq!(vec![Reason::Incomplete(vec!["reviews".into()])], evaluate(&Policy::default(), pr));
+    }
+}
@@ -0,0 +1,251 @@
+use bot::command::Command;
+
+/// Merge queue state of a single PR.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    number: u64,
+    /// User the PR is approved for merging by, `None` if only its priority
+    /// or rollup flag has been set so far.
+    approver: Option<String>,
+    priority: i32,
+    rollup: bool,
+    /// Head SHA the last merge attempt has failed at. The PR is not tried
+    /// again until either its head moves or `retry` is given.
+    failed: Option<String>,
+    /// Approval order, which breaks ties.
+    seq: u64,
+}
+
+impl Entry {
+    pub fn number(&self) -> u64 {
+        self.number
+    }
+
+    pub fn approver(&self) -> Option<&str> {
+        self.approver.as_ref().map(|v| v.as_str())
+    }
+
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    pub fn is_rollup(&self) -> bool {
+        self.rollup
+    }
+
+    /// Returns `true` if the last merge attempt has failed at the given head.
+    pub fn has_failed(&self, head: &str) -> bool {
+        self.failed.as_ref().map(|v| v.as_str()) == Some(head)
+    }
+
+    /// Returns `true` if the PR may be merged at the given head.
+    pub fn is_ready(&self, head: &str) -> bool {
+        self.approver.is_some() && !self.has_failed(head)
+    }
+}
+
+/// PRs approved for merging via comment commands of a single repository,
+/// along with failed merge attempts of any PRs.
+///
+/// The queue is kept in memory, so approvals must be given again after
+/// restart.
+#[derive(Debug, Default)]
+pub struct Queue {
+    entries: Vec<Entry>,
+    seq: u64,
+}
+
+impl Queue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn entry(&mut self, number: u64) -> &mut Entry {
+        match self.entries.iter().position(|v| v.number == number) {
+            Some(id) => &mut self.entries[id],
+            None => {
+                self.entries.push(Entry {
+                    number,
+                    approver: None,
+                    priority: 0,
+                    rollup: false,
+                    failed: None,
+                    seq: 0,
+                });
+                self.entries.last_mut().expect("entry has just been pushed")
+            }
+        }
+    }
+
+    /// Applies the command given by the user for the PR.
+    pub fn apply(&mut self, number: u64, user: &str, command: &Command) -> Result<(), String> {
+        match command {
+            Command::Approve(approver) => {
+                self.seq += 1;
+                let seq = self.seq;
+                let entry = self.entry(number);
+                if entry.approver.is_none() {
+                    entry.seq = seq;
+                }
+                entry.approver = Some(approver.clone().unwrap_or_else(|| user.into()));
+                entry.failed = None;
+            }
+            Command::Retry => {
+                let entry = self.entries
+                    .iter_mut()
+                    .find(|v| v.number == number && (v.approver.is_some() || v.failed.is_some()))
+                    .ok_or_else(|| format!("#{} is not in the merge queue", number))?;
+                entry.failed = None;
+            }
+            Command::Cancel => self.remove(number),
+            Command::Priority(priority) => self.entry(number).priority = *priority,
+            Command::Rollup => self.entry(number).rollup = true,
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, number: u64) -> Option<&Entry> {
+        self.entries.iter().find(|v| v.number == number)
+    }
+
+    /// Returns approved PRs in merge order: higher priority first, then
+    /// non-rollups, then in the order of approval.
+    pub fn ordered(&self) -> Vec<&Entry> {
+        let mut entries: Vec<&Entry> = self.entries.iter().filter(|v| v.approver.is_some()).collect();
+        entries.sort_by_key(|v| (-(v.priority as i64), v.rollup, v.seq));
+        entries
+    }
+
+    /// Returns the zero-based position of the approved PR in merge order.
+    pub fn position(&self, number: u64) -> Option<usize> {
+        self.ordered().iter().position(|v| v.number == number)
+    }
+
+    /// Records a failed merge attempt at the given head, also for PRs merged
+    /// without approval commands.
+    pub fn fail(&mut self, number: u64, head: &str) {
+        self.entry(number).failed = Some(head.into());
+    }
+
+    pub fn remove(&mut self, number: u64) {
+        self.entries.retain(|v| v.number != number);
+    }
+
+    /// Forgets PRs that are no longer open.
+    pub fn retain_open(&mut self, open: &[u64]) {
+        self.entries.retain(|v| open.contains(&v.number));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Queue;
+    use bot::command::Command;
+
+    fn numbers(queue: &Queue) -> Vec<u64> {
+        queue.ordered().iter().map(|v| v.number()).collect()
+    }
+
+    #[test]
+    fn orders_by_priority_rollup_and_approval() {
+        let mut queue = Queue::new();
+        queue.apply(1, "alice", &Command::Approve(None)).unwrap();
+        queue.apply(2, "alice", &Command::Rollup).unwrap();
+        queue.apply(2, "alice", &Command::Approve(None)).unwrap();
+        queue.apply(3, "alice", &Command::Approve(None)).unwrap();
+        queue.apply(4, "alice", &Command::Priority(5)).unwrap();
+        queue.apply(4, "alice", &Command::Approve(None)).unwrap();
+        queue.apply(5, "alice", &Command::Priority(10)).unwrap();
+
+        assert_eq!(vec![4, 1, 3, 2], numbers(&queue));
+        assert_eq!(Some(0), queue.position(4));
+        assert_eq!(None, queue.position(5));
+    }
+
+    #[test]
+    fn keeps_approval_order_on_reapproval() {
+        let mut queue = Queue::new();
+        queue.apply(1, "alice", &Command::Approve(None)).unwrap();
+        queue.apply(2, "alice", &Command::Approve(None)).unwrap();
+        queue.apply(1, "bob", &Command::Approve(Some("carol".into()))).unwrap();
+
+        assert_eq!(vec![1, 2], numbers(&queue));
+        assert_eq!(Some("carol"), queue.get(1).and_then(|v| v.approver()));
+    }
+
+    #[test]
+    fn approves_on_behalf_of_commenter() {
+        let mut queue = Queue::new();
+        queue.apply(1, "alice", &Command::Approve(None)).unwrap();
+        assert_eq!(Some("alice"), queue.get(1).and_then(|v| v.approver()));
+    }
+
+    #[test]
+    fn cancel_removes_entry() {
+        let mut queue = Queue::new();
+        queue.apply(1, "alice", &Command::Approve(None)).unwrap();
+        queue.apply(1, "alice", &Command::Cancel).unwrap();
+
+        assert!(queue.get(1).is_none());
+        assert_eq!(Vec::<u64>::new(), numbers(&queue));
+    }
+
+    #[test]
+    fn failed_head_is_not_ready_until_retry_or_push() {
+        let mut queue = Queue::new();
+        queue.apply(1, "alice", &Command::Approve(None)).unwrap();
+        queue.fail(1, "a");
+
+        let entry = queue.get(1).unwrap();
+        assert!(entry.has_failed("a"));
+        assert!(!entry.is_ready("a"));
+        assert!(entry.is_ready("b"));
+
+        queue.apply(1, "alice", &Command::Retry).unwrap();
+        assert!(queue.get(1).unwrap().is_ready("a"));
+    }
+
+    #[test]
+    fn reapproval_clears_failure() {
+        let mut queue = Queue::new();
+        queue.apply(1, "alice", &Command::Approve(None)).unwrap();
+        queue.fail(1, "a");
+        queue.apply(1, "alice", &Command::Approve(None)).unwrap();
+
+        assert!(queue.get(1).unwrap().is_ready("a"));
+    }
+
+    #[test]
+    fn records_failures_of_unapproved_prs() {
+        let mut queue = Queue::new();
+        queue.fail(1, "a");
+
+        let entry = queue.get(1).unwrap();
+        assert!(entry.has_failed("a"));
+        assert!(!entry.is_ready("b"));
+        assert_eq!(Vec::<u64>::new(), numbers(&queue));
+
+        queue.apply(1, "alice", &Command::Retry).unwrap();
+        assert!(!queue.get(1).unwrap().has_failed("a"));
+    }
+
+    #[test]
+    fn retry_requires_entry() {
+        let mut queue = Queue::new();
+        assert!(queue.apply(1, "alice", &Command::Retry).is_err());
+
+        queue.apply(1, "alice", &Command::Priority(1)).unwrap();
+        assert!(queue.apply(1, "alice", &Command::Retry).is_err());
+    }
+
+    #[test]
+    fn forgets_closed_prs() {
+        let mut queue = Queue::new();
+        queue.apply(1, "alice", &Command::Approve(None)).unwrap();
+        queue.apply(2, "alice", &Command::Approve(None)).unwrap();
+        queue.retain_open(&[2]);
+
+        assert_eq!(vec![2], numbers(&queue));
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use actix::Arbiter;
+use futures::sync::mpsc;
+
+use audit::Audit;
+use bot::policy::Policy;
+use bot::worker::Worker;
+use config::MergeBotConfig;
+use github::Client;
+use server::Hook;
+
+/// Capacity of each worker queue.
+///
+/// Hooks that do not fit are dropped, which is fine, because workers also
+/// re-evaluate their repositories periodically.
+const WORKER_QUEUE_CAPACITY: usize = 64;
+
+/// A running worker as seen by the router.
+struct Handle {
+    tx: mpsc::Sender<Hook>,
+    /// Policy shared with the worker, which allows to swap it in place.
+    policy: Arc<RwLock<Policy>>,
+}
+
+/// Routes hooks to per-repository workers by `repository.full_name`.
+///
+/// Workers for explicitly configured repositories are spawned at start, while
+/// repositories matched by a wildcard are served since their first hook.
+pub struct Router {
+    cfg: MergeBotConfig,
+    client: Client,
+    audit: Audit,
+    /// Worker queues by lowercase repository full name.
+    workers: HashMap<String, Handle>,
+}
+
+impl Router {
+    pub fn new(cfg: MergeBotConfig, client: Client, audit: Audit) -> Self {
+        Self {
+            cfg,
+            client,
+            audit,
+            workers: HashMap::new(),
+        }
+    }
+
+    /// Spawns workers for repositories listed without wildcards.
+    ///
+    /// Must be called within a running system.
+    pub fn start(&mut self) {
+        let names: Vec<String> = self.cfg
+            .repositories()
+            .iter()
+            .filter(|v| !v.name().is_wildcard())
+            .map(|v| v.name().as_str().to_owned())
+            .collect();
+
+        for name in names {
+            self.spawn(&name);
+        }
+    }
+
+    fn spawn(&mut self, name: &str) -> bool {
+        let key = name.to_lowercase();
+        if self.workers.contains_key(&key) {
+            return true;
+        }
+
+        let policy = match self.cfg.policy(name) {
+            Some(policy) => policy,
+            None => return false,
+        };
+
+        let (tx, rx) = mpsc::channel(WORKER_QUEUE_CAPACITY);
+        let policy = Arc::new(RwLock::new(policy));
+        let worker = Worker::new(name, policy.clone(), self.client.clone(), self.audit.clone(), &self.cfg);
+        Arbiter::handle().spawn(worker.run(rx));
+
+        info!("started worker for {}", name);
+        self.workers.insert(key, Handle { tx, policy });
+        true
+    }
+
+    /// Applies reloaded settings.
+    ///
+    /// Policies of running workers are swapped in place, workers of no longer
+    /// served repositories are stopped after finishing their current pass and
+    /// workers for newly listed repositories are started.
+    pub fn reconfigure(&mut self, cfg: MergeBotConfig) {
+        if cfg.interval() != self.cfg.interval() {
+            warn!("changed `merge.interval` applies to newly started workers only");
+        }
+
+        if cfg.login() != self.cfg.login() || cfg.status() != self.cfg.status() || cfg.checklist() != self.cfg.checklist() {
+            warn!("changed `merge.login`, `merge.status` and `merge.checklist` apply to newly started workers only");
+        }
+
+        self.cfg = cfg;
+
+        let cfg = &self.cfg;
+        self.workers.retain(|name, handle| match cfg.policy(name) {
+            Some(policy) => {
+                *handle.policy.write().expect("policy lock is poisoned") = policy;
+                true
+            }
+            None => {
+                info!("stopped serving {}", name);
+                false
+            }
+        });
+
+        self.start();
+    }
+
+    /// Routes the hook to the worker serving its repository, spawning one if
+    /// required.
+    pub fn route(&mut self, hook: Hook) {
+        // Webhooks of a GitHub App carry the installation serving the
+        // repository.
+        self.client.authenticator().observe(&hook.payload);
+
+        let name = match hook.payload["repository"]["full_name"].as_str() {
+            Some(name) => name.to_owned(),
+            None => return,
+        };
+
+        if !self.spawn(&name) {
+            debug!("ignored `{}` event for unserved repository {}", hook.event, name);
+            return;
+        }
+
+        let key = name.to_lowercase();
+        let result = match self.workers.get_mut(&key) {
+            Some(handle) => handle.tx.try_send(hook),
+            None => return,
+        };
+
+        if let Err(err) = result {
+            if err.is_full() {
+                warn!("worker queue for {} is full, dropped hook", name);
+            } else {
+                error!("worker for {} has stopped", name);
+                self.workers.remove(&key);
+            }
+        }
+    }
+}
@@ -0,0 +1,1027 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use actix_web::http::StatusCode;
+use futures::prelude::*;
+use futures::{future, stream};
+use futures::sync::mpsc;
+use serde_json::Value;
+use tokio::timer::{Delay, Interval};
+
+use audit::{Action, Audit, Record};
+use bot::checklist::{self, MARKER};
+use bot::command;
+use bot::owners::{self, CodeOwners, Requirement};
+use bot::policy::{Decision, Mode, Policy, Reviewers, Strategy};
+use bot::queue::{Entry, Queue};
+use config::{MergeBotConfig, StatusConfig};
+use github::{
+    Client, CommitState, Error, MergeInput, MergeRequest, MergeableState, PullRequest, Reaction, StatusInput,
+    MAX_FILES,
+};
+use logging::{self, Context};
+use metrics;
+use server::Hook;
+
+/// Events that may change the merge readiness of PRs.
+const RELEVANT_EVENTS: &[&str] = &[
+    "check_suite",
+    "issue_comment",
+    "pull_request",
+    "pull_request_review",
+    "push",
+    "status",
+];
+
+enum Tick {
+    Hook(Hook),
+    Timer,
+    Closed,
+}
+
+/// Number of times the mergeability of a PR is requested until GitHub has
+/// computed it.
+const MERGEABLE_POLL_ATTEMPTS: u32 = 5;
+
+/// Delay in seconds before the second mergeability request, doubled after
+/// each one.
+const MERGEABLE_POLL_DELAY: u64 = 1;
+
+/// Label of PRs, whose branches can not be updated because of conflicts.
+const NEEDS_REBASE: &str = "needs-rebase";
+
+/// Result of updating a PR branch with its base.
+enum Update {
+    /// The branch is up to date, so the PR may be merged.
+    Current,
+    /// The base has been merged into the branch, so status checks must run
+    /// again.
+    Updated,
+    Conflict,
+}
+
+/// Returns `true` if the user may be requested to review, i.e. is neither
+/// excluded nor away.
+fn is_eligible(user: &str, excluded: &[String], reviewers: &Reviewers) -> bool {
+    !excluded.iter().any(|v| v.eq_ignore_ascii_case(user)) && !reviewers.is_away(user)
+}
+
+/// Returns the eligible candidate with the fewest pending review requests,
+/// preferring earlier ones.
+fn least_loaded(
+    candidates: &[String],
+    excluded: &[String],
+    reviewers: &Reviewers,
+    load: &HashMap<String, usize>,
+) -> Option<String> {
+    candidates
+        .iter()
+        .filter(|v| is_eligible(v, excluded, reviewers))
+        .min_by_key(|v| load.get(&v.to_lowercase()).cloned().unwrap_or_default())
+        .cloned()
+}
+
+/// Maximum length of a commit status description.
+const MAX_DESCRIPTION: usize = 140;
+
+fn truncate(description: String) -> String {
+    if description.chars().count() <= MAX_DESCRIPTION {
+        return description;
+    }
+
+    let mut result: String = description.chars().take(MAX_DESCRIPTION - 1).collect();
+    result.push('…');
+    result
+}
+
+/// Serves a single repository, evaluating its open PRs against the policy
+/// and merging ready ones.
+///
+/// PRs are evaluated each time a relevant hook arrives and periodically, so
+/// that dropped hooks do not stall the repository.
+#[derive(Clone)]
+pub struct Worker {
+    owner: String,
+    name: String,
+    /// Policy, which may be swapped while the worker is running.
+    policy: Arc<RwLock<Policy>>,
+    client: Client,
+    audit: Audit,
+    interval: Duration,
+    /// Login the bot is mentioned by in comment commands.
+    login: String,
+    status: StatusConfig,
+    queue: Arc<Mutex<Queue>>,
+    /// Statuses published by PR number along with the head they were
+    /// published for, so that unchanged ones are not published again.
+    published: Arc<Mutex<HashMap<u64, (String, StatusInput)>>>,
+    checklist: bool,
+    /// Checklist comment ids and bodies by PR number.
+    comments: Arc<Mutex<HashMap<u64, (u64, String)>>>,
+    /// Actions announced in the shadow mode by PR number, so that each one
+    /// is commented once.
+    shadowed: Arc<Mutex<HashMap<u64, String>>>,
+    /// Files changed by PRs by PR number along with the head they were
+    /// listed for.
+    files: Arc<Mutex<HashMap<u64, (String, Vec<String>)>>>,
+    /// Position in the team of the next reviewer picked in turn.
+    cursor: Arc<Mutex<usize>>,
+}
+
+impl Worker {
+    /// Constructs a new worker for the repository with the given full name,
+    /// i.e. `owner/name`.
+    pub fn new(
+        repository: &str,
+        policy: Arc<RwLock<Policy>>,
+        client: Client,
+        audit: Audit,
+        cfg: &MergeBotConfig,
+    ) -> Self {
+        let mut parts = repository.splitn(2, '/');
+        let owner = parts.next().unwrap_or_default().into();
+        let name = parts.next().unwrap_or_default().into();
+
+        Self {
+            owner,
+            name,
+            policy,
+            client,
+            audit,
+            interval: cfg.interval(),
+            login: cfg.login().into(),
+            status: cfg.status().clone(),
+            queue: Arc::new(Mutex::new(Queue::new())),
+            published: Arc::new(Mutex::new(HashMap::new())),
+            checklist: cfg.checklist(),
+            comments: Arc::new(Mutex::new(HashMap::new())),
+            shadowed: Arc::new(Mutex::new(HashMap::new())),
+            files: Arc::new(Mutex::new(HashMap::new())),
+            cursor: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    fn repository(&self) -> String {
+        format!("{}/{}", self.owner, self.name)
+    }
+
+    /// Records the action taken on the PR along with the GitHub response.
+    fn record<T>(&self, number: u64, head: &str, action: Action, reason: String, result: &Result<T, Error>) {
+        let mode = self.policy.read().expect("policy lock is poisoned").mode();
+        let response = match result {
+            Ok(..) => "ok".into(),
+            Err(err) => format!("{:?}", err),
+        };
+
+        let record = Record::event(&self.repository(), number, head, mode, reason).with_action(action, response);
+        self.audit.record(&record);
+    }
+
+    /// Runs the worker until the hooks sender is dropped.
+    #[async]
+    pub fn run(self, rx: mpsc::Receiver<Hook>) -> Result<(), ()> {
+        let ticks = Interval::new(Instant::now(), self.interval)
+            .map(|_| Tick::Timer)
+            .map_err(|err| error!("worker timer failed: {}", err));
+        let stream = rx
+            .map(Tick::Hook)
+            .chain(stream::once(Ok(Tick::Closed)))
+            .select(ticks);
+
+        #[async]
+        for tick in stream {
+            let (context, hook) = match tick {
+                Tick::Hook(hook) => {
+                    if !RELEVANT_EVENTS.contains(&hook.event.as_str()) {
+                        continue;
+                    }
+                    (Context::default().with_delivery(hook.delivery.clone()), Some(hook))
+                }
+                Tick::Timer => (Context::default(), None),
+                Tick::Closed => break,
+            };
+
+            let (comment, pull_request) = match hook {
+                Some(Hook { ref event, ref payload, .. }) if event == "issue_comment" => (Some(payload.clone()), None),
+                Some(Hook { ref event, ref payload, .. }) if event == "pull_request" => (None, Some(payload.clone())),
+                _ => (None, None),
+            };
+
+            if let Some(payload) = pull_request {
+                let future = logging::scoped(context.clone(), self.clone().assign(payload));
+                if let Err(err) = await!(future.then(Ok::<_, ()>))? {
+                    warn!("failed to request reviewers in {}/{}: {:?}", self.owner, self.name, err);
+                }
+            }
+
+            if let Some(payload) = comment {
+                let future = logging::scoped(context.clone(), self.clone().command(payload));
+                match await!(future.then(Ok::<_, ()>))? {
+                    Ok(true) => {}
+                    Ok(false) => continue,
+                    Err(err) => {
+                        warn!("failed to handle command in {}/{}: {:?}", self.owner, self.name, err);
+                        continue;
+                    }
+                }
+            }
+
+            let future = logging::scoped(context, self.clone().process());
+            if let Err(err) = await!(future.then(Ok::<_, ()>))? {
+                warn!("failed to process {}/{}: {:?}", self.owner, self.name, err);
+            }
+        }
+
+        info!("worker for {}/{} has stopped", self.owner, self.name);
+        Ok(())
+    }
+
+    /// Handles comment commands addressed to the bot, acknowledging them
+    /// with a reaction or explaining in a reply why they were rejected.
+    ///
+    /// Returns `true` if the merge queue might have changed.
+    #[async]
+    fn command(self, payload: Value) -> Result<bool, Error> {
+        // Issue comments are delivered for both issues and PRs.
+        if payload["action"] != "created" || payload["issue"]["pull_request"].is_null() {
+            return Ok(false);
+        }
+
+        let number = payload["issue"]["number"].as_u64().unwrap_or_default();
+        let id = payload["comment"]["id"].as_u64().unwrap_or_default();
+        let user = payload["comment"]["user"]["login"].as_str().unwrap_or_default().to_owned();
+        let body = payload["comment"]["body"].as_str().unwrap_or_default().to_owned();
+
+        if user.eq_ignore_ascii_case(&self.login) {
+            return Ok(false);
+        }
+
+        let commands = match command::parse(&body, &self.login) {
+            Ok(commands) => commands,
+            Err(err) => {
+                let reply = format!("@{}: {}", user, err);
+                await!(self.clone().acknowledge(number, id, Reaction::Confused, Some(reply)))?;
+                return Ok(false);
+            }
+        };
+
+        if commands.is_empty() {
+            return Ok(false);
+        }
+
+        let permission = await!(self.client.repository(&self.owner, &self.name).permission(&user))?;
+        if !permission.can_push() {
+            info!("ignored commands of {} without push access to {}/{}", user, self.owner, self.name);
+            let reply = format!("@{}: only users with push access may give commands", user);
+            await!(self.clone().acknowledge(number, id, Reaction::ThumbsDown, Some(reply)))?;
+            return Ok(false);
+        }
+
+        let errors: Vec<String> = {
+            let mut queue = self.queue.lock().expect("queue lock is poisoned");
+            commands.iter().filter_map(|v| queue.apply(number, &user, v).err()).collect()
+        };
+
+        let commands: Vec<String> = commands.iter().map(|v| v.to_string()).collect();
+        info!("{} gave `{}` for {}/{}#{}", user, commands.join(" "), self.owner, self.name, number);
+
+        if errors.is_empty() {
+            await!(self.clone().acknowledge(number, id, Reaction::ThumbsUp, None))?;
+        } else {
+            let reply = format!("@{}: {}", user, errors.join("; "));
+            await!(self.clone().acknowledge(number, id, Reaction::Confused, Some(reply)))?;
+        }
+
+        Ok(true)
+    }
+
+    /// Reacts to the command comment, replying if there is something to
+    /// explain.
+    #[async]
+    fn acknowledge(self, number: u64, id: u64, reaction: Reaction, reply: Option<String>) -> Result<(), Error> {
+        let mode = self.policy.read().expect("policy lock is poisoned").mode();
+        if mode != Mode::Live {
+            info!("dry run: would react {:?} to the comment in {}/{}#{}", reaction, self.owner, self.name, number);
+            return Ok(());
+        }
+
+        let api = self.client.pull_request(&self.owner, &self.name);
+        let result = await!(api.react(id, reaction));
+        self.record(number, "", Action::React, format!("react {:?} to a command", reaction), &result);
+        result?;
+
+        if let Some(reply) = reply {
+            let result = await!(api.comment(number, &reply));
+            self.record(number, "", Action::Comment, "reply to a command".into(), &result);
+            result?;
+        }
+
+        Ok(())
+    }
+
+    /// Logs the action skipped in a dry run mode, announcing it on the PR in
+    /// the shadow mode unless it has already been announced.
+    #[async]
+    fn skip(self, mode: Mode, number: u64, head: String, action: String) -> Result<(), Error> {
+        info!("dry run: would {} in {}/{}#{}", action, self.owner, self.name, number);
+        if mode != Mode::Shadow {
+            return Ok(());
+        }
+
+        {
+            let mut shadowed = self.shadowed.lock().expect("shadowed lock is poisoned");
+            if shadowed.get(&number) == Some(&action) {
+                return Ok(());
+            }
+            shadowed.insert(number, action.clone());
+        }
+
+        let api = self.client.pull_request(&self.owner, &self.name);
+        let result = await!(api.comment(number, &format!("Shadow mode: the bot would {} now.", action)));
+        self.record(number, &head, Action::Shadow, action, &result);
+        result.map(|_| ())
+    }
+
+    /// Edits the checklist comment of the PR in place, posting it the first
+    /// time.
+    #[async]
+    fn update_checklist(self, number: u64, head: String, body: String) -> Result<(), Error> {
+        let api = self.client.pull_request(&self.owner, &self.name);
+
+        let cached = self.comments
+            .lock()
+            .expect("comments lock is poisoned")
+            .get(&number)
+            .map(|(id, _)| *id);
+
+        // The comment is looked up by its marker after restart.
+        let id = match cached {
+            Some(id) => Some(id),
+            None => await!(api.clone().comments(number))?
+                .into_iter()
+                .find(|v| v.body.starts_with(MARKER))
+                .map(|v| v.id),
+        };
+
+        let result = match id {
+            Some(id) => await!(api.edit_comment(id, &body)),
+            None => await!(api.comment(number, &body)),
+        };
+        self.record(number, &head, Action::Comment, "update the checklist".into(), &result);
+
+        let mut comments = self.comments.lock().expect("comments lock is poisoned");
+        match result {
+            Ok(comment) => {
+                comments.insert(number, (comment.id, body));
+                Ok(())
+            }
+            Err(err) => {
+                // The comment may have been deleted, so look it up again.
+                comments.remove(&number);
+                Err(err)
+            }
+        }
+    }
+
+    /// Requests mergeability of the PR with exponential backoff until GitHub
+    /// has computed it, giving up with `Unknown` after a few attempts.
+    #[async]
+    fn settle(self, number: u64) -> Result<MergeableState, Error> {
+        let api = self.client.pull_request(&self.owner, &self.name);
+        let mut delay = Duration::from_secs(MERGEABLE_POLL_DELAY);
+
+        for attempt in 1..MERGEABLE_POLL_ATTEMPTS + 1 {
+            let state = await!(api.mergeable(number))?;
+            if state != MergeableState::Unknown || attempt == MERGEABLE_POLL_ATTEMPTS {
+                debug!("{}/{}#{} is {:?} after {} attempt(s)", self.owner, self.name, number, state, attempt);
+                return Ok(state);
+            }
+
+            await!(Delay::new(Instant::now() + delay).then(|_| Ok::<_, Error>(())))?;
+            delay *= 2;
+        }
+
+        Ok(MergeableState::Unknown)
+    }
+
+    /// Describes the decision as a commit status of the PR head.
+    fn describe(&self, pr: &PullRequest, decision: &Decision, entry: Option<&Entry>, policy: &Policy) -> StatusInput {
+        let (state, description) = match decision {
+            Decision::Block(reasons) => {
+                if reasons.iter().any(|v| v.is_failure()) {
+                    (CommitState::Failure, decision.to_string())
+                } else {
+                    (CommitState::Pending, decision.to_string())
+                }
+            }
+            Decision::Merge => match entry {
+                Some(entry) if entry.has_failed(&pr.head_ref_oid) => (
+                    CommitState::Failure,
+                    format!("merge failed, comment `@{} retry` to try again", self.login),
+                ),
+                Some(entry) if entry.approver().is_some() => (CommitState::Success, decision.to_string()),
+                Some(..) | None if policy.require_command() => {
+                    (CommitState::Pending, format!("waiting for `@{} r+`", self.login))
+                }
+                Some(..) | None => (CommitState::Success, decision.to_string()),
+            },
+        };
+
+        StatusInput {
+            state,
+            target_url: Some(self.status.details_url(&self.owner, &self.name, pr.number, &pr.url)),
+            description: truncate(description),
+            context: self.status.context(&self.login),
+        }
+    }
+
+    /// Merges the base branch into the PR branch if it is behind.
+    ///
+    /// Conflicting PRs are labelled with `needs-rebase` and their authors are
+    /// asked to rebase. The label is removed once the branch is up to date.
+    /// Branches of forks are left as is, because the bot can not push there.
+    #[async]
+    fn update(self, pr: PullRequest, mode: Mode) -> Result<Update, Error> {
+        let api = self.client.pull_request(&self.owner, &self.name);
+        let labelled = pr.labels().any(|v| v == NEEDS_REBASE);
+
+        let repository = self.client.repository(&self.owner, &self.name);
+        let comparison = await!(repository.compare(&pr.base_ref_name, &pr.head_ref_oid))?;
+        if !comparison.is_behind() || pr.is_cross_repository {
+            if comparison.is_behind() {
+                debug!(
+                    "{}/{}#{} is behind `{}`, but comes from a fork",
+                    self.owner, self.name, pr.number, pr.base_ref_name
+                );
+            } else if labelled && mode != Mode::Live {
+                let action = format!("remove the `{}` label", NEEDS_REBASE);
+                await!(self.clone().skip(mode, pr.number, pr.head_ref_oid.clone(), action))?;
+            } else if labelled {
+                let result = await!(api.remove_label(pr.number, NEEDS_REBASE));
+                let reason = format!("remove the `{}` label", NEEDS_REBASE);
+                self.record(pr.number, &pr.head_ref_oid, Action::Label, reason, &result);
+                result?;
+            }
+            return Ok(Update::Current);
+        }
+
+        let record = Record::new(&self.repository(), &pr, &Decision::Merge, mode);
+
+        // Conflicts are only detected by merging, so dry runs assume there
+        // are none.
+        if mode != Mode::Live {
+            self.audit.record(&record.with_action(Action::Update, "skipped in dry run".into()));
+            let action = format!("merge `{}` into this branch", pr.base_ref_name);
+            await!(self.clone().skip(mode, pr.number, pr.head_ref_oid.clone(), action))?;
+            return Ok(Update::Updated);
+        }
+
+        let request = MergeRequest {
+            owner: self.owner.clone(),
+            repo: self.name.clone(),
+            body: MergeInput {
+                base: pr.head_ref_name.clone(),
+                head: pr.base_ref_name.clone(),
+                commit_message: format!("Merge branch '{}' into {}", pr.base_ref_name, pr.head_ref_name),
+            },
+        };
+
+        let result = await!(self.client.merge(request));
+        let response = match &result {
+            Ok(..) => "updated".into(),
+            Err(Error::Unexpected(StatusCode::CONFLICT)) => "conflict".into(),
+            Err(err) => format!("{:?}", err),
+        };
+        self.audit.record(&record.with_action(Action::Update, response));
+
+        match result {
+            Ok(..) => {
+                info!("merged `{}` into {}/{}#{}", pr.base_ref_name, self.owner, self.name, pr.number);
+                if labelled {
+                    let result = await!(api.remove_label(pr.number, NEEDS_REBASE));
+                    let reason = format!("remove the `{}` label", NEEDS_REBASE);
+                    self.record(pr.number, &pr.head_ref_oid, Action::Label, reason, &result);
+                    result?;
+                }
+                Ok(Update::Updated)
+            }
+            Err(Error::Unexpected(StatusCode::CONFLICT)) => {
+                warn!("`{}` conflicts with {}/{}#{}", pr.base_ref_name, self.owner, self.name, pr.number);
+                if !labelled {
+                    let result = await!(api.add_label(pr.number, NEEDS_REBASE));
+                    let reason = format!("add the `{}` label", NEEDS_REBASE);
+                    self.record(pr.number, &pr.head_ref_oid, Action::Label, reason, &result);
+                    result?;
+
+                    let message = format!(
+                        "`{}` can not be merged into this branch because of conflicts, please rebase.",
+                        pr.base_ref_name
+                    );
+                    let result = await!(api.comment(pr.number, &message));
+                    self.record(pr.number, &pr.head_ref_oid, Action::Comment, "ask to rebase".into(), &result);
+                    result?;
+                }
+                Ok(Update::Conflict)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Requests reviewers of a PR that has just been opened or marked ready
+    /// for review, as configured by the policy.
+    #[async]
+    fn assign(self, payload: Value) -> Result<(), Error> {
+        let action = payload["action"].as_str().unwrap_or_default().to_owned();
+        let draft = payload["pull_request"]["draft"].as_bool().unwrap_or_default();
+        if (action != "opened" && action != "ready_for_review") || draft {
+            return Ok(());
+        }
+
+        let policy = self.policy.read().expect("policy lock is poisoned").clone();
+        let reviewers = policy.reviewers().clone();
+        if !reviewers.enabled() {
+            return Ok(());
+        }
+
+        let number = payload["pull_request"]["number"].as_u64().unwrap_or_default();
+        let head = payload["pull_request"]["head"]["sha"].as_str().unwrap_or_default().to_owned();
+        let author = payload["pull_request"]["user"]["login"].as_str().unwrap_or_default().to_owned();
+        let requested: Vec<String> = payload["pull_request"]["requested_reviewers"]
+            .as_array()
+            .map(|v| v.iter().filter_map(|v| v["login"].as_str()).map(|v| v.to_owned()).collect())
+            .unwrap_or_default();
+
+        let mut excluded = requested.clone();
+        excluded.push(author);
+        excluded.push(self.login.clone());
+
+        let api = self.client.pull_request(&self.owner, &self.name);
+        let pull_requests = await!(api.list())?;
+
+        let mut load = HashMap::new();
+        for login in pull_requests.iter().flat_map(|v| v.requested_reviewers()) {
+            *load.entry(login.to_lowercase()).or_insert(0) += 1;
+        }
+
+        let chosen = match reviewers.strategy() {
+            Strategy::CodeOwners => {
+                let pr = match pull_requests.into_iter().find(|v| v.number == number) {
+                    Some(pr) => pr,
+                    None => return Ok(()),
+                };
+
+                // Owners of PRs with too many files are unknown, so none are
+                // requested.
+                let requirements = await!(self.clone().owners(vec![pr]))?
+                    .pop()
+                    .and_then(|v| v)
+                    .unwrap_or_default();
+                let mut chosen: Vec<String> = Vec::new();
+                for requirement in requirements {
+                    if requirement.is_satisfied_by(requested.iter().chain(chosen.iter()).map(|v| v.as_str())) {
+                        continue;
+                    }
+                    if let Some(login) = least_loaded(requirement.logins(), &excluded, &reviewers, &load) {
+                        chosen.push(login);
+                    }
+                }
+                chosen
+            }
+            strategy => {
+                let (org, team) = match reviewers.team() {
+                    Some((org, team)) => (org.to_owned(), team.to_owned()),
+                    None => return Ok(()),
+                };
+                let repository = self.client.repository(&self.owner, &self.name);
+                let members = await!(repository.team_members(org, team))?;
+
+                let needed = (reviewers.count() as usize).saturating_sub(requested.len());
+                let mut chosen = Vec::new();
+                if strategy == Strategy::RoundRobin {
+                    let mut cursor = self.cursor.lock().expect("cursor lock is poisoned");
+                    let start = *cursor;
+                    for offset in 0..members.len() {
+                        if chosen.len() == needed {
+                            break;
+                        }
+                        let id = (start + offset) % members.len();
+                        if is_eligible(&members[id], &excluded, &reviewers) {
+                            chosen.push(members[id].clone());
+                            *cursor = id + 1;
+                        }
+                    }
+                } else {
+                    let mut candidates = members;
+                    while chosen.len() < needed {
+                        match least_loaded(&candidates, &excluded, &reviewers, &load) {
+                            Some(login) => {
+                                candidates.retain(|v| *v != login);
+                                chosen.push(login);
+                            }
+                            None => break,
+                        }
+                    }
+                }
+                chosen
+            }
+        };
+
+        if chosen.is_empty() {
+            return Ok(());
+        }
+
+        let mentions: Vec<String> = chosen.iter().map(|v| format!("@{}", v)).collect();
+        let action = format!("request review from {}", mentions.join(", "));
+        if policy.mode() != Mode::Live {
+            return await!(self.clone().skip(policy.mode(), number, head, action));
+        }
+
+        info!("requesting review of {}/{}#{} from {}", self.owner, self.name, number, mentions.join(", "));
+        let result = await!(api.request_reviewers(number, chosen));
+        self.record(number, &head, Action::RequestReviewers, action, &result);
+        result
+    }
+
+    /// Returns `CODEOWNERS` of the given base branch, which is empty if
+    /// there is no such file.
+    #[async]
+    fn codeowners(self, base: String) -> Result<CodeOwners, Error> {
+        let repository = self.client.repository(&self.owner, &self.name);
+        for path in owners::LOCATIONS {
+            if let Some(contents) = await!(repository.contents(path, &base))? {
+                match contents.decode() {
+                    Some(text) => return Ok(CodeOwners::parse(&text)),
+                    None => warn!("failed to decode {} of {}/{}@{}", path, self.owner, self.name, base),
+                }
+            }
+        }
+
+        Ok(CodeOwners::default())
+    }
+
+    /// Returns `CODEOWNERS` requirements of each PR, or `None` if it changes
+    /// more files than GitHub lists.
+    ///
+    /// Teams that can not be resolved have no members, so that only other
+    /// owners of their patterns may approve. Patterns left without any such
+    /// owners fail the policy.
+    #[async]
+    fn owners(self, pull_requests: Vec<PullRequest>) -> Result<Vec<Option<Vec<Requirement>>>, Error> {
+        let api = self.client.pull_request(&self.owner, &self.name);
+        let repository = self.client.repository(&self.owner, &self.name);
+
+        let mut codeowners: HashMap<String, CodeOwners> = HashMap::new();
+        let mut teams: HashMap<String, Vec<String>> = HashMap::new();
+        let mut result = Vec::new();
+        for pr in pull_requests {
+            if !codeowners.contains_key(&pr.base_ref_name) {
+                let file = await!(self.clone().codeowners(pr.base_ref_name.clone()))?;
+                codeowners.insert(pr.base_ref_name.clone(), file);
+            }
+
+            let cached = self.files
+                .lock()
+                .expect("files lock is poisoned")
+                .get(&pr.number)
+                .and_then(|(head, files)| if *head == pr.head_ref_oid { Some(files.clone()) } else { None });
+
+            let files = match cached {
+                Some(files) => files,
+                None => {
+                    let files = await!(api.clone().files(pr.number))?;
+                    let mut cache = self.files.lock().expect("files lock is poisoned");
+                    cache.insert(pr.number, (pr.head_ref_oid.clone(), files.clone()));
+                    files
+                }
+            };
+
+            if files.len() >= MAX_FILES {
+                result.push(None);
+                continue;
+            }
+
+            let rules: Vec<_> = codeowners[&pr.base_ref_name]
+                .covering(files.iter().map(|v| v.as_str()))
+                .into_iter()
+                .cloned()
+                .collect();
+
+            let mut requirements = Vec::new();
+            for rule in rules {
+                // Emails can not be matched with reviewers.
+                let names: Vec<String> = rule.owners()
+                    .iter()
+                    .filter(|v| v.starts_with('@'))
+                    .map(|v| v[1..].to_owned())
+                    .collect();
+
+                let mut logins = Vec::new();
+                for name in names {
+                    let id = match name.find('/') {
+                        Some(id) => id,
+                        None => {
+                            logins.push(name);
+                            continue;
+                        }
+                    };
+
+                    if !teams.contains_key(&name) {
+                        let (org, team) = (name[..id].to_owned(), name[id + 1..].to_owned());
+                        let members = match await!(repository.clone().team_members(org, team)) {
+                            Ok(members) => members,
+                            Err(err) => {
+                                warn!("failed to get members of @{}: {:?}", name, err);
+                                Vec::new()
+                            }
+                        };
+                        teams.insert(name.clone(), members);
+                    }
+                    logins.extend(teams[&name].iter().cloned());
+                }
+                requirements.push(Requirement::new(rule, logins));
+            }
+            result.push(Some(requirements));
+        }
+
+        Ok(result)
+    }
+
+    /// Evaluates open PRs, publishing their statuses and checklists and
+    /// merging the first ready one.
+    ///
+    /// PRs approved with comment commands go first in the queue order,
+    /// followed by other ready PRs, oldest first, unless the policy requires
+    /// commands. At most one PR is merged or updated per turn, because
+    /// either changes the base or the head, which invalidates status checks.
+    #[async]
+    fn process(self) -> Result<(), Error> {
+        let policy = self.policy.read().expect("policy lock is poisoned").clone();
+        let context = self.status.context(&self.login);
+        let api = self.client.pull_request(&self.owner, &self.name);
+        let mut pull_requests = await!(api.list())?;
+
+        // Deciding on unknown mergeability would hold PRs for a whole
+        // interval, so wait for GitHub to compute it.
+        let unknown = pull_requests
+            .iter()
+            .enumerate()
+            .filter(|(_, pr)| pr.mergeable == MergeableState::Unknown)
+            .map(|(id, pr)| self.clone().settle(pr.number).then(move |result| Ok::<_, ()>((id, result))))
+            .collect::<Vec<_>>();
+
+        for (id, result) in await!(future::join_all(unknown)).unwrap_or_default() {
+            match result {
+                Ok(state) => pull_requests[id].mergeable = state,
+                Err(err) => warn!(
+                    "failed to get mergeability of {}/{}#{}: {:?}",
+                    self.owner, self.name, pull_requests[id].number, err
+                ),
+            }
+        }
+
+        // New pushes make earlier approvals stale, which is what
+        // `synchronize` events signal. Dismissed reviews are no longer
+        // approvals, so each one is dismissed once. Without fresh approvals
+        // stale ones still count, so they are kept.
+        if policy.fresh_approvals() && policy.dismiss_stale() {
+            let stale: Vec<(u64, u64, String)> = pull_requests
+                .iter()
+                .flat_map(|pr| {
+                    policy
+                        .stale_approvals(pr)
+                        .into_iter()
+                        .filter_map(|v| v.database_id)
+                        .map(|id| (pr.number, id, pr.head_ref_oid.clone()))
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+
+            for (number, id, head) in stale {
+                if policy.mode() != Mode::Live {
+                    let reason = format!("dismiss stale review {}", id);
+                    let record = Record::event(&self.repository(), number, &head, policy.mode(), reason.clone());
+                    self.audit.record(&record.with_action(Action::Dismiss, "skipped in dry run".into()));
+                    if let Err(err) = await!(self.clone().skip(policy.mode(), number, head, reason)) {
+                        warn!(
+                            "failed to announce skipped dismissal in {}/{}#{}: {:?}",
+                            self.owner, self.name, number, err
+                        );
+                    }
+                    continue;
+                }
+
+                let message = format!(
+                    "New commits were pushed since this approval, the head is {} now. Please review again.",
+                    head
+                );
+                let result = await!(api.dismiss_review(number, id, &message));
+                self.record(number, &head, Action::Dismiss, format!("dismiss stale review {}", id), &result);
+                match result {
+                    Ok(()) => info!("dismissed stale review {} of {}/{}#{}", id, self.owner, self.name, number),
+                    Err(err) => warn!(
+                        "failed to dismiss stale review {} of {}/{}#{}: {:?}",
+                        id, self.owner, self.name, number, err
+                    ),
+                }
+            }
+        }
+
+        let owners = if policy.code_owners() {
+            await!(self.clone().owners(pull_requests.clone()))?
+        } else {
+            vec![Some(Vec::new()); pull_requests.len()]
+        };
+
+        let decisions: Vec<Decision> = pull_requests
+            .iter()
+            .zip(owners.iter())
+            .map(|(pr, owners)| {
+                let decision = policy.evaluate(pr, &context, owners.as_ref().map(|v| v.as_slice()));
+                debug!("{}/{}#{}: {}", self.owner, self.name, pr.number, decision);
+                self.audit.record(&Record::new(&self.repository(), pr, &decision, policy.mode()));
+                decision
+            })
+            .collect();
+
+        let (candidates, statuses, checklists) = {
+            let mut queue = self.queue.lock().expect("queue lock is poisoned");
+            let open: Vec<u64> = pull_requests.iter().map(|v| v.number).collect();
+            queue.retain_open(&open);
+            self.audit.retain_open(&self.repository(), &open);
+
+            let ready: Vec<&PullRequest> = pull_requests
+                .iter()
+                .zip(decisions.iter())
+                .filter(|(_, decision)| decision.is_merge())
+                .map(|(pr, _)| pr)
+                .collect();
+
+            let ordered = queue.ordered();
+            metrics::MERGE_QUEUE_LENGTH
+                .with_label_values(&[&self.repository()])
+                .set(ordered.len() as i64);
+            let queued = ordered.iter().filter_map(|entry| {
+                ready
+                    .iter()
+                    .find(|pr| pr.number == entry.number() && entry.is_ready(&pr.head_ref_oid))
+            });
+            let unqueued = ready.iter().filter(|pr| {
+                queue
+                    .get(pr.number)
+                    .map_or(true, |v| v.approver().is_none() && !v.has_failed(&pr.head_ref_oid))
+            });
+
+            let candidates: Vec<PullRequest> = if policy.require_command() {
+                queued.map(|pr| (*pr).clone()).collect()
+            } else {
+                queued.chain(unqueued).map(|pr| (*pr).clone()).collect()
+            };
+
+            let mut published = self.published.lock().expect("statuses lock is poisoned");
+            published.retain(|number, _| open.contains(number));
+
+            let statuses: Vec<(u64, String, StatusInput)> = if self.status.enabled() {
+                pull_requests
+                    .iter()
+                    .zip(decisions.iter())
+                    .map(|(pr, decision)| {
+                        let status = self.describe(pr, decision, queue.get(pr.number), &policy);
+                        (pr.number, pr.head_ref_oid.clone(), status)
+                    })
+                    .filter(|(number, sha, status)| {
+                        published.get(number).map_or(true, |(prev, published)| prev != sha || published != status)
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+
+            let mut comments = self.comments.lock().expect("comments lock is poisoned");
+            comments.retain(|number, _| open.contains(number));
+
+            self.shadowed
+                .lock()
+                .expect("shadowed lock is poisoned")
+                .retain(|number, _| open.contains(number));
+
+            self.files
+                .lock()
+                .expect("files lock is poisoned")
+                .retain(|number, _| open.contains(number));
+
+            let checklists: Vec<(u64, String, String)> = if self.checklist {
+                pull_requests
+                    .iter()
+                    .zip(decisions.iter())
+                    .map(|(pr, decision)| {
+                        let position = queue.position(pr.number);
+                        let body = checklist::render(decision, &policy, position, &self.login);
+                        (pr.number, pr.head_ref_oid.clone(), body)
+                    })
+                    .filter(|(number, _, body)| comments.get(number).map_or(true, |(_, prev)| prev != body))
+                    .collect()
+            } else {
+                Vec::new()
+            };
+
+            (candidates, statuses, checklists)
+        };
+
+        let live = policy.mode() == Mode::Live;
+
+        for (number, sha, status) in statuses {
+            if !live {
+                debug!(
+                    "dry run: would set {}/{}#{} status to {:?}: {}",
+                    self.owner, self.name, number, status.state, status.description
+                );
+                continue;
+            }
+
+            let result = await!(api.set_status(&sha, status.clone()));
+            let reason = format!("set status to {:?}: {}", status.state, status.description);
+            self.record(number, &sha, Action::Status, reason, &result);
+            match result {
+                Ok(()) => {
+                    let mut published = self.published.lock().expect("statuses lock is poisoned");
+                    published.insert(number, (sha, status));
+                }
+                Err(err) => {
+                    warn!("failed to publish status of {}/{}#{}: {:?}", self.owner, self.name, number, err);
+                }
+            }
+        }
+
+        for (number, head, body) in checklists {
+            if !live {
+                debug!("dry run: would update checklist of {}/{}#{}", self.owner, self.name, number);
+                continue;
+            }
+
+            if let Err(err) = await!(self.clone().update_checklist(number, head, body)) {
+                warn!("failed to update checklist of {}/{}#{}: {:?}", self.owner, self.name, number, err);
+            }
+        }
+
+        for pr in candidates {
+            if policy.update() {
+                match await!(self.clone().update(pr.clone(), policy.mode()))? {
+                    Update::Current => {}
+                    Update::Updated => break,
+                    Update::Conflict => continue,
+                }
+            }
+
+            let record = Record::new(&self.repository(), &pr, &Decision::Merge, policy.mode());
+
+            if !live {
+                self.audit.record(&record.with_action(Action::Merge, "skipped in dry run".into()));
+                let action = format!("merge this PR at {}", pr.head_ref_oid);
+                await!(self.clone().skip(policy.mode(), pr.number, pr.head_ref_oid.clone(), action))?;
+                break;
+            }
+
+            info!("merging {}/{}#{} at {}", self.owner, self.name, pr.number, pr.head_ref_oid);
+
+            let result = await!(api.merge(pr.number, &pr.head_ref_oid));
+            let response = match &result {
+                Ok(value) => value.to_string(),
+                Err(err) => format!("{:?}", err),
+            };
+            self.audit.record(&record.with_action(Action::Merge, response));
+
+            match result {
+                Ok(..) => {
+                    metrics::MERGES.with_label_values(&["success"]).inc();
+                    self.queue.lock().expect("queue lock is poisoned").remove(pr.number);
+                    info!("merged {}/{}#{}", self.owner, self.name, pr.number);
+                    break;
+                }
+                Err(err) => {
+                    metrics::MERGES.with_label_values(&["failure"]).inc();
+
+                    warn!("failed to merge {}/{}#{}: {:?}", self.owner, self.name, pr.number, err);
+
+                    // The PR is not tried again at the same head, so that it
+                    // does not block others, and reviewers must know that it
+                    // is stuck.
+                    self.queue
+                        .lock()
+                        .expect("queue lock is poisoned")
+                        .fail(pr.number, &pr.head_ref_oid);
+
+                    let message = format!("Merge failed: {:?}. Push a fix or comment `@{} retry`.", err, self.login);
+                    let result = await!(api.comment(pr.number, &message));
+                    let reason = "report the failed merge".into();
+                    self.record(pr.number, &pr.head_ref_oid, Action::Comment, reason, &result);
+                    if let Err(err) = result {
+                        warn!("failed to comment on {}/{}#{}: {:?}", self.owner, self.name, pr.number, err);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,529 @@
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::net::TcpListener;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+use serde_ignored;
+use serde_yaml::Value;
+
+use super::{layers, Config, GithubConfig, ListenAddr, MergeBotConfig, NetworkConfig, Overrides, Scope, TlsConfig};
+use bot::policy::Strategy;
+use github::Credentials;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    /// Suspicious, but the bot is able to run with it.
+    Warning,
+    Error,
+}
+
+/// A single problem found in the configuration.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    severity: Severity,
+    message: String,
+}
+
+impl Diagnostic {
+    fn error<T: Into<String>>(message: T) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+        }
+    }
+
+    fn warning<T: Into<String>>(message: T) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        match self.severity {
+            Severity::Warning => write!(fmt, "warning: {}", self.message),
+            Severity::Error => write!(fmt, "error: {}", self.message),
+        }
+    }
+}
+
+/// Problems found while checking the configuration.
+#[derive(Debug, Default)]
+pub struct Report {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Report {
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    pub fn errors(&self) -> usize {
+        self.count(Severity::Error)
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.count(Severity::Warning)
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.diagnostics.iter().filter(|v| v.severity == severity).count()
+    }
+
+    fn push(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+}
+
+/// Checks the configuration file at the given path with overrides applied.
+///
+/// Besides parsing, which is all `Config::load` does, this reports unknown
+/// keys, which are silently ignored otherwise, and settings that are valid
+/// on their own, but make no sense either in this environment or together.
+pub fn check<P: AsRef<Path>>(path: P, overrides: &Overrides) -> Report {
+    let path = path.as_ref();
+    let mut report = Report::default();
+
+    let content = match Config::read(path) {
+        Ok(content) => content,
+        Err(err) => {
+            report.push(Diagnostic::error(err.to_string()));
+            return report;
+        }
+    };
+
+    let value = match Config::tree(path, &content, overrides) {
+        Ok(value) => value,
+        Err(err) => {
+            report.push(Diagnostic::error(err.to_string()));
+            return report;
+        }
+    };
+
+    // Typos in optional keys are otherwise silently replaced with defaults.
+    // Overrides with typos are reported as well.
+    if Config::is_legacy(&content) {
+        report.push(Diagnostic::warning(
+            "`merge.github` is deprecated, move the section to the top level `github`",
+        ));
+    }
+
+    check_github_keys(&value, &mut report);
+    let _: Result<Config, _> = serde_ignored::deserialize(layers::Coerce(value), |key| {
+        report.push(Diagnostic::error(format!("unknown key `{}`", key)));
+    });
+
+    let cfg = match Config::parse(path, &content, overrides) {
+        Ok(cfg) => cfg,
+        Err(err) => {
+            report.push(Diagnostic::error(err.to_string()));
+            return report;
+        }
+    };
+
+    if cfg.hook().secret().expose().is_empty() {
+        report.push(Diagnostic::error("`hook.secret` is empty, so signatures can not be verified"));
+    }
+
+    let allowlist = cfg.hook().allowlist();
+    if allowlist.github_meta() && allowlist.refresh_interval().as_secs() == 0 {
+        report.push(Diagnostic::error("`hook.allowlist.refresh_interval` must be positive"));
+    }
+
+    if !allowlist.github_meta() && allowlist.cache().is_some() {
+        report.push(Diagnostic::warning(
+            "`hook.allowlist.cache` is unused, because `github_meta` is disabled",
+        ));
+    }
+
+    if allowlist.github_meta() && allowlist.cache().is_none() {
+        report.push(Diagnostic::warning(
+            "`hook.allowlist.cache` is not specified, so hooks are rejected after restart until GitHub ranges are fetched",
+        ));
+    }
+
+    check_network(cfg.network(), &mut report);
+    check_github(cfg.github(), &mut report);
+    check_merge(cfg.merge(), &mut report);
+
+    report
+}
+
+/// Keys of the `github` section, which `serde_ignored` does not see, because
+/// credentials are flattened into it.
+const GITHUB_KEYS: &[&str] = &["base_url", "user_agent", "oauth_token", "app"];
+
+/// Keys of the `github.app` section.
+const GITHUB_APP_KEYS: &[&str] = &["id", "private_key"];
+
+fn check_github_keys(value: &Value, report: &mut Report) {
+    let github = match value.as_mapping().and_then(|v| v.get(&Value::String("github".into()))) {
+        Some(github) => github,
+        None => return,
+    };
+
+    let sections = vec![
+        ("github", Some(github), GITHUB_KEYS),
+        (
+            "github.app",
+            github.as_mapping().and_then(|v| v.get(&Value::String("app".into()))),
+            GITHUB_APP_KEYS,
+        ),
+    ];
+
+    for (prefix, section, known) in sections {
+        let mapping = match section.and_then(|v| v.as_mapping()) {
+            Some(mapping) => mapping,
+            None => continue,
+        };
+
+        for key in mapping.iter().map(|(key, _)| key) {
+            match key.as_str() {
+                Some(key) if known.contains(&key) => {}
+                Some(key) => report.push(Diagnostic::error(format!("unknown key `{}.{}`", prefix, key))),
+                None => report.push(Diagnostic::error(format!("`{}` keys must be strings", prefix))),
+            }
+        }
+    }
+}
+
+fn check_network(cfg: &NetworkConfig, report: &mut Report) {
+    if cfg.backlog() <= 0 {
+        report.push(Diagnostic::error("`network.backlog` must be positive"));
+    }
+
+    if cfg.workers() == Some(0) {
+        report.push(Diagnostic::error("`network.workers` must be positive"));
+    }
+
+    if cfg.max_payload() == 0 {
+        report.push(Diagnostic::error("`network.max_payload` must be positive"));
+    }
+
+    let listeners = cfg.listeners();
+    if listeners.is_empty() {
+        report.push(Diagnostic::error("neither `network.addr` nor `network.listeners` is specified"));
+    }
+
+    if !listeners.iter().any(|v| v.serves(Scope::Hook)) {
+        report.push(Diagnostic::warning("no listener serves hooks"));
+    }
+
+    for listener in &listeners {
+        if let ListenAddr::Tcp { addr, .. } = listener.addr() {
+            if listener.serves(Scope::Admin) && !addr.ip().is_loopback() {
+                report.push(Diagnostic::warning(format!(
+                    "{} serves unauthenticated `admin` endpoints, prefer a Unix socket or loopback",
+                    addr
+                )));
+            }
+        }
+
+        check_listener(listener.addr(), report);
+    }
+}
+
+fn check_listener(addr: &ListenAddr, report: &mut Report) {
+    match addr {
+        ListenAddr::Tcp { addr, tls } => {
+            if let Some(tls) = tls {
+                check_tls(tls, report);
+            }
+
+            match TcpListener::bind(addr) {
+                Ok(..) => {}
+                Err(ref err) if err.kind() == io::ErrorKind::AddrInUse => {
+                    report.push(Diagnostic::warning(format!(
+                        "{} is already in use, which is fine only if the bot is running",
+                        addr
+                    )));
+                }
+                Err(err) => {
+                    report.push(Diagnostic::error(format!("unable to bind on {}: {}", addr, err)));
+                }
+            }
+        }
+        ListenAddr::Unix { path, .. } => {
+            let parent = path.parent().filter(|v| !v.as_os_str().is_empty()).unwrap_or(Path::new("."));
+            if !parent.is_dir() {
+                report.push(Diagnostic::error(format!(
+                    "directory of the socket `{}` does not exist",
+                    path.display()
+                )));
+            }
+
+            if path.exists() && UnixStream::connect(path).is_ok() {
+                report.push(Diagnostic::warning(format!(
+                    "socket `{}` is already in use, which is fine only if the bot is running",
+                    path.display()
+                )));
+            }
+        }
+    }
+}
+
+fn check_tls(tls: &TlsConfig, report: &mut Report) {
+    let mut files = vec![("cert", tls.cert()), ("key", tls.key())];
+    files.extend(tls.client_ca().map(|v| ("client_ca", v)));
+
+    for (key, path) in files {
+        if !path.is_file() {
+            report.push(Diagnostic::error(format!(
+                "TLS `{}` `{}` does not exist",
+                key,
+                path.display()
+            )));
+        }
+    }
+
+    if tls.reload_interval().as_secs() == 0 {
+        report.push(Diagnostic::error("TLS `reload_interval` must be positive"));
+    }
+}
+
+fn check_github(cfg: &GithubConfig, report: &mut Report) {
+    let url = cfg.base_url.as_url();
+
+    if url.scheme() != "https" && url.scheme() != "http" {
+        report.push(Diagnostic::error(format!("`github.base_url` must be an HTTP(S) URL, got `{}`", url)));
+    } else if url.host().is_none() {
+        report.push(Diagnostic::error("`github.base_url` has no host"));
+    } else if url.path() != "/" || url.query().is_some() {
+        report.push(Diagnostic::error(format!(
+            "`github.base_url` must not contain path or query, got `{}`",
+            url
+        )));
+    }
+
+    if let Credentials::App { app } = &cfg.credentials {
+        if !app.private_key.is_file() {
+            report.push(Diagnostic::error(format!(
+                "`github.app.private_key` `{}` does not exist",
+                app.private_key.display()
+            )));
+        }
+    }
+}
+
+fn check_merge(cfg: &MergeBotConfig, report: &mut Report) {
+    if cfg.interval().as_secs() == 0 {
+        report.push(Diagnostic::error("`merge.interval` must be positive"));
+    }
+
+    if cfg.login().is_empty() {
+        report.push(Diagnostic::error("`merge.login` is empty, so comment commands are never recognized"));
+    }
+
+    if cfg.status().enabled() && cfg.status().context(cfg.login()).is_empty() {
+        report.push(Diagnostic::error("`merge.status.context` is empty"));
+    }
+
+    if cfg.repositories().is_empty() {
+        report.push(Diagnostic::warning("`merge.repositories` is empty, so nothing is served"));
+    }
+
+    for (id, repository) in cfg.repositories().iter().enumerate() {
+        let name = repository.name();
+        let policy = repository.policy().apply(&cfg.policy);
+
+        if policy.wip().is_empty() {
+            report.push(Diagnostic::error(format!(
+                "`wip` marker of `{}` is empty, which marks every PR as a work in progress",
+                name.as_str()
+            )));
+        }
+
+        if policy.approvals() == 0 && !policy.checks() {
+            report.push(Diagnostic::warning(format!(
+                "`{}` requires neither approvals nor checks, so every PR is merged",
+                name.as_str()
+            )));
+        }
+
+        if policy.dismiss_stale() && !policy.fresh_approvals() {
+            report.push(Diagnostic::warning(format!(
+                "`{}` dismisses stale approvals, but counts them without `fresh_approvals`, so none are dismissed",
+                name.as_str()
+            )));
+        }
+
+        let reviewers = policy.reviewers();
+        if reviewers.enabled() && reviewers.strategy() != Strategy::CodeOwners && reviewers.team().is_none() {
+            report.push(Diagnostic::error(format!(
+                "reviewers of `{}` are picked from a team, but `reviewers.team` is not like `org/team`",
+                name.as_str()
+            )));
+        }
+
+        if !name.is_wildcard() && repository.exclude.iter().any(|v| v.matches(name.as_str())) {
+            report.push(Diagnostic::error(format!(
+                "`{}` is excluded by its own `exclude` patterns",
+                name.as_str()
+            )));
+        }
+
+        // The first matching entry wins, so entries covered by the previous
+        // ones are never used.
+        let shadowed = cfg.repositories()[..id].iter().find(|v| {
+            v.name() == name || (!name.is_wildcard() && v.matches(name.as_str()))
+        });
+
+        if let Some(prev) = shadowed {
+            report.push(Diagnostic::error(format!(
+                "`{}` is already matched by `{}` above, so its settings are never used",
+                name.as_str(),
+                prev.name().as_str()
+            )));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use super::{check, Report, Severity};
+    use config::Overrides;
+
+    const CONFIG: &str = r#"
+network:
+  addr: ["127.0.0.1", 0]
+hook:
+  secret: s3cr3t
+github:
+  oauth_token: 0123456789abcdef0123456789abcdef01234567
+merge:
+  interval: 60
+  repositories:
+    - name: sonm-io/core
+"#;
+
+    fn run(name: &str, content: &str) -> Report {
+        run_with(name, content, &Overrides::new())
+    }
+
+    fn run_with(name: &str, content: &str, overrides: &Overrides) -> Report {
+        let path: PathBuf = env::temp_dir().join(format!("sonmhub-check-{}-{}.yaml", name, ::std::process::id()));
+        File::create(&path).unwrap().write_all(content.as_bytes()).unwrap();
+        let report = check(&path, overrides);
+        fs::remove_file(&path).unwrap();
+        report
+    }
+
+    fn messages(report: &Report, severity: Severity) -> Vec<String> {
+        report
+            .diagnostics()
+            .iter()
+            .filter(|v| v.severity() == severity)
+            .map(|v| v.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn accepts_minimal_config() {
+        let report = run("minimal", CONFIG);
+        assert_eq!(Vec::<String>::new(), messages(&report, Severity::Error));
+    }
+
+    #[test]
+    fn reports_unknown_keys() {
+        let report = run("unknown", &CONFIG.replace("  interval: 60", "  interval: 60\n  intreval: 30"));
+        assert_eq!(vec!["error: unknown key `merge.intreval`"], messages(&report, Severity::Error));
+    }
+
+    #[test]
+    fn reports_positions_despite_overrides() {
+        let mut overrides = Overrides::new();
+        overrides.set("hook.secret=0v3rr1dd3n").unwrap();
+
+        let report = run_with("position", &CONFIG.replace("interval: 60", "interval: soon"), &overrides);
+        let errors = messages(&report, Severity::Error);
+
+        assert_eq!(1, errors.len());
+        assert!(errors[0].contains("line 9"), "{}", errors[0]);
+    }
+
+    #[test]
+    fn reports_unknown_github_keys() {
+        let content = CONFIG.replace(
+            "  oauth_token: 0123456789abcdef0123456789abcdef01234567",
+            "  oauth_token: 0123456789abcdef0123456789abcdef01234567\n  base_ulr: https://github.example.com",
+        );
+        let report = run("github", &content);
+        assert_eq!(vec!["error: unknown key `github.base_ulr`"], messages(&report, Severity::Error));
+    }
+
+    #[test]
+    fn accepts_deprecated_github_section() {
+        let content = CONFIG
+            .replace("github:\n  oauth_token: 0123456789abcdef0123456789abcdef01234567\n", "")
+            .replace(
+                "  interval: 60\n",
+                "  interval: 60\n  github:\n    oauth_token: 0123456789abcdef0123456789abcdef01234567\n",
+            );
+        let report = run("legacy", &content);
+
+        assert_eq!(Vec::<String>::new(), messages(&report, Severity::Error));
+        assert!(messages(&report, Severity::Warning)
+            .contains(&"warning: `merge.github` is deprecated, move the section to the top level `github`".to_owned()));
+    }
+
+    #[test]
+    fn rejects_both_github_sections() {
+        let content = CONFIG.replace(
+            "  interval: 60\n",
+            "  interval: 60\n  github:\n    oauth_token: 0123456789abcdef0123456789abcdef01234567\n",
+        );
+        let report = run("both", &content);
+
+        assert_eq!(1, messages(&report, Severity::Error).len());
+    }
+
+    #[test]
+    fn warns_about_dismissing_stale_approvals_that_count() {
+        let content = CONFIG.replace("  interval: 60\n", "  interval: 60\n  policy:\n    dismiss_stale: true\n");
+        let report = run("dismiss", &content);
+
+        assert_eq!(Vec::<String>::new(), messages(&report, Severity::Error));
+        assert!(messages(&report, Severity::Warning).iter().any(|v| v.contains("fresh_approvals")));
+    }
+
+    #[test]
+    fn reports_empty_secret_and_missing_repositories() {
+        let content = CONFIG
+            .replace("secret: s3cr3t", "secret: \"\"")
+            .replace("  repositories:\n    - name: sonm-io/core\n", "  repositories: []\n");
+        let report = run("empty", &content);
+
+        assert_eq!(
+            vec!["error: `hook.secret` is empty, so signatures can not be verified"],
+            messages(&report, Severity::Error)
+        );
+        assert!(messages(&report, Severity::Warning)
+            .contains(&"warning: `merge.repositories` is empty, so nothing is served".to_owned()));
+    }
+
+    #[test]
+    fn reports_shadowed_repositories() {
+        let content = CONFIG.replace(
+            "    - name: sonm-io/core\n",
+            "    - name: sonm-io/*\n    - name: sonm-io/core\n",
+        );
+        let report = run("shadowed", &content);
+
+        assert_eq!(
+            vec!["error: `sonm-io/core` is already matched by `sonm-io/*` above, so its settings are never used"],
+            messages(&report, Severity::Error)
+        );
+    }
+}
@@ -0,0 +1,445 @@
+use std::env;
+use std::mem;
+
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Unexpected, Visitor};
+use serde_yaml::{self, Error, Mapping, Value};
+
+use super::ConfigError;
+
+/// Prefix of environment variables overriding config values.
+const ENV_PREFIX: &str = "SONMHUB_";
+
+/// Separator of path segments in environment variable names.
+const ENV_SEPARATOR: &str = "__";
+
+/// A single override of the value at the given path.
+#[derive(Clone, Debug)]
+struct Override {
+    path: Vec<String>,
+    value: Value,
+    /// Where the override came from, for error messages.
+    source: String,
+}
+
+/// Values overriding the configuration file, applied in order.
+///
+/// Values are strings, which are converted only where fields expect other
+/// types, see `Coerce`, so numbers, booleans and flow style sequences, like
+/// `["::", 8080]`, work there, while secrets like `123456` stay intact.
+#[derive(Clone, Debug, Default)]
+pub struct Overrides {
+    overrides: Vec<Override>,
+}
+
+impl Overrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.overrides.is_empty()
+    }
+
+    /// Collects `SONMHUB_*` environment variables, where `__` separates path
+    /// segments, for example `SONMHUB_GITHUB__OAUTH_TOKEN` overrides the
+    /// `github.oauth_token` value.
+    pub fn from_env() -> Self {
+        let mut overrides = Self::new();
+
+        let mut vars: Vec<(String, String)> = env::vars().filter(|(name, ..)| name.starts_with(ENV_PREFIX)).collect();
+        vars.sort();
+
+        for (name, value) in vars {
+            let path = name[ENV_PREFIX.len()..]
+                .split(ENV_SEPARATOR)
+                .map(|v| v.to_lowercase())
+                .collect();
+            overrides.push(path, &value, name);
+        }
+
+        overrides
+    }
+
+    /// Adds an override in the `key.path=value` form, as given by the
+    /// `--set` flag.
+    pub fn set(&mut self, arg: &str) -> Result<(), ConfigError> {
+        let mut parts = arg.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) if !key.is_empty() => {
+                let path = key.split('.').map(|v| v.to_owned()).collect();
+                self.push(path, value, format!("--set {}", key));
+                Ok(())
+            }
+            _ => Err(ConfigError::Override(arg.into(), "expected `key=value`".into())),
+        }
+    }
+
+    fn push(&mut self, path: Vec<String>, value: &str, source: String) {
+        self.overrides.push(Override {
+            path,
+            value: Value::String(value.into()),
+            source,
+        });
+    }
+
+    /// Applies overrides to the given config tree.
+    pub fn apply(&self, mut value: Value) -> Result<Value, ConfigError> {
+        for item in &self.overrides {
+            value = assign(value, &item.path, item.value.clone())
+                .map_err(|err| ConfigError::Override(item.source.clone(), err))?;
+        }
+
+        Ok(value)
+    }
+}
+
+fn assign(value: Value, path: &[String], new: Value) -> Result<Value, String> {
+    let (key, rest) = match path.split_first() {
+        Some(v) => v,
+        None => return Ok(new),
+    };
+
+    match value {
+        Value::Mapping(mut mapping) => {
+            let key = Value::String(key.clone());
+            let child = mapping.remove(&key).unwrap_or(Value::Null);
+            mapping.insert(key, assign(child, rest, new)?);
+            Ok(Value::Mapping(mapping))
+        }
+        Value::Sequence(mut sequence) => match key.parse::<usize>() {
+            Ok(id) if id < sequence.len() => {
+                let child = mem::replace(&mut sequence[id], Value::Null);
+                sequence[id] = assign(child, rest, new)?;
+                Ok(Value::Sequence(sequence))
+            }
+            _ => Err(format!("`{}` is not an index of a sequence with {} items", key, sequence.len())),
+        },
+        Value::Null => {
+            let mut mapping = Mapping::new();
+            mapping.insert(Value::String(key.clone()), assign(Value::Null, rest, new)?);
+            Ok(Value::Mapping(mapping))
+        }
+        _ => Err(format!("can not set `{}` of a scalar", key)),
+    }
+}
+
+/// Replaces `${NAME}` references in all string values with the environment
+/// variables they name, while `$${` stands for a literal `${`.
+///
+/// Results remain strings, which `Coerce` converts where fields expect
+/// other types, so that `backlog: ${BACKLOG}` still works.
+pub fn interpolate(value: Value) -> Result<Value, ConfigError> {
+    let value = match value {
+        Value::String(string) => Value::String(substitute(&string)?),
+        Value::Sequence(sequence) => {
+            Value::Sequence(sequence.into_iter().map(interpolate).collect::<Result<_, _>>()?)
+        }
+        Value::Mapping(mapping) => {
+            let mut result = Mapping::new();
+            for (key, value) in mapping {
+                result.insert(key, interpolate(value)?);
+            }
+            Value::Mapping(result)
+        }
+        value => value,
+    };
+
+    Ok(value)
+}
+
+fn substitute(string: &str) -> Result<String, ConfigError> {
+    let mut result = String::new();
+    let mut rest = string;
+
+    while let Some(pos) = rest.find('$') {
+        result.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+
+        if rest.starts_with("$${") {
+            result.push_str("${");
+            rest = &rest[3..];
+        } else if rest.starts_with("${") {
+            let end = rest
+                .find('}')
+                .ok_or_else(|| ConfigError::Env(rest.into(), "unterminated reference".into()))?;
+            result.push_str(&var(&rest[2..end])?);
+            rest = &rest[end + 1..];
+        } else {
+            result.push('$');
+            rest = &rest[1..];
+        }
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
+
+fn var(name: &str) -> Result<String, ConfigError> {
+    env::var(name).map_err(|err| ConfigError::Env(name.into(), err.to_string()))
+}
+
+/// Implements deserializer methods of scalar types, parsing strings.
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                match self.0 {
+                    Value::String(string) => match string.trim().parse() {
+                        Ok(value) => visitor.$visit(value),
+                        Err(..) => Err(de::Error::invalid_type(Unexpected::Str(&string), &visitor)),
+                    },
+                    value => value.$method(visitor),
+                }
+            }
+        )*
+    };
+}
+
+/// Implements deserializer methods, which need no conversion.
+macro_rules! deserialize_forwarded {
+    ($($method:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                self.deserialize_any(visitor)
+            }
+        )*
+    };
+}
+
+/// Deserializer of a config tree, which converts strings to the types that
+/// fields expect.
+///
+/// Interpolated variables and overrides are always strings, because their
+/// type is unknown until the field they set asks for one. Types buffered by
+/// serde, like untagged enums, see strings as is.
+pub struct Coerce(pub Value);
+
+impl Coerce {
+    /// Parses a string in the YAML flow style, where a collection is expected.
+    fn collection(self) -> Result<Self, Error> {
+        match self.0 {
+            Value::String(ref string) if string.trim_left().starts_with('[') || string.trim_left().starts_with('{') => {
+                serde_yaml::from_str(string).map(Coerce)
+            }
+            value => Ok(Coerce(value)),
+        }
+    }
+}
+
+impl<'de> Deserializer<'de> for Coerce {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Value::Sequence(sequence) => visitor.visit_seq(Items(sequence.into_iter())),
+            Value::Mapping(mapping) => visitor.visit_map(Entries {
+                entries: mapping.into_iter().collect::<Vec<_>>().into_iter(),
+                value: None,
+            }),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    deserialize_forwarded! {
+        deserialize_char,
+        deserialize_str,
+        deserialize_string,
+        deserialize_bytes,
+        deserialize_byte_buf,
+        deserialize_unit,
+        deserialize_identifier,
+        deserialize_ignored_any,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Value::Null => visitor.visit_none(),
+            value => visitor.visit_some(Coerce(value)),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.collection()?.deserialize_any(visitor)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.collection()?.deserialize_any(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.collection()?.deserialize_any(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.collection()?.deserialize_any(visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.collection()?.deserialize_any(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.0.deserialize_enum(name, variants, visitor)
+    }
+}
+
+struct Items(::std::vec::IntoIter<Value>);
+
+impl<'de> SeqAccess<'de> for Items {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+        match self.0.next() {
+            Some(value) => seed.deserialize(Coerce(value)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+struct Entries {
+    entries: ::std::vec::IntoIter<(Value, Value)>,
+    /// Value of the last returned key.
+    value: Option<Value>,
+}
+
+impl<'de> MapAccess<'de> for Entries {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(Coerce(key)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        match self.value.take() {
+            Some(value) => seed.deserialize(Coerce(value)),
+            None => Err(de::Error::custom("value is missing")),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+
+    use serde::Deserialize;
+    use serde_yaml::{self, Value};
+
+    use super::{interpolate, Coerce, Overrides};
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Sample {
+        secret: String,
+        backlog: i32,
+        enabled: bool,
+        addr: (String, u16),
+        limit: Option<u64>,
+    }
+
+    fn sample(overrides: &[&str]) -> Result<Sample, serde_yaml::Error> {
+        let mut layers = Overrides::new();
+        for item in overrides {
+            layers.set(item).unwrap();
+        }
+
+        let value = serde_yaml::from_str("{secret: x, backlog: 1, enabled: false, addr: [a, 1]}").unwrap();
+        Sample::deserialize(Coerce(layers.apply(value).unwrap()))
+    }
+
+    #[test]
+    fn overrides_keep_strings() {
+        for secret in &["123456", "true", "1e10", "a: b", "~"] {
+            let sample = sample(&[&format!("secret={}", secret)]).unwrap();
+            assert_eq!(*secret, sample.secret);
+        }
+    }
+
+    #[test]
+    fn overrides_coerce_other_types() {
+        let sample = sample(&["backlog=42", "enabled=true", "addr=[\"::\", 8080]", "limit=7"]).unwrap();
+
+        assert_eq!(42, sample.backlog);
+        assert!(sample.enabled);
+        assert_eq!(("::".to_owned(), 8080), sample.addr);
+        assert_eq!(Some(7), sample.limit);
+    }
+
+    #[test]
+    fn overrides_reject_invalid_values() {
+        assert!(sample(&["backlog=many"]).is_err());
+        assert!(sample(&["enabled=1"]).is_err());
+    }
+
+    #[test]
+    fn interpolation_keeps_strings() {
+        env::set_var("SONMHUB_TEST_SECRET", "123456");
+        env::set_var("SONMHUB_TEST_BACKLOG", "42");
+
+        let value: Value = serde_yaml::from_str(
+            "{secret: '${SONMHUB_TEST_SECRET}', backlog: '${SONMHUB_TEST_BACKLOG}', enabled: true, addr: [a, 1]}",
+        ).unwrap();
+        let value = interpolate(value).unwrap();
+        let secret = value.as_mapping().and_then(|v| v.get(&Value::String("secret".into())));
+        assert_eq!(Some(&Value::String("123456".into())), secret);
+
+        let sample = Sample::deserialize(Coerce(value)).unwrap();
+        assert_eq!("123456", sample.secret);
+        assert_eq!(42, sample.backlog);
+    }
+
+    #[test]
+    fn interpolation_escapes_references() {
+        let value = interpolate(Value::String("$${HOME}".into())).unwrap();
+        assert_eq!(Value::String("${HOME}".into()), value);
+    }
+}
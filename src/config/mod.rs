@@ -0,0 +1,927 @@
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{self, Read};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+use log::LevelFilter;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_yaml::{self, Value};
+
+pub use self::check::{check, Diagnostic, Report, Severity};
+pub use self::layers::Overrides;
+use allowlist::Cidr;
+pub use bot::policy::{Policy, PolicyOverrides};
+pub use github::Config as GithubConfig;
+use secure::Secret;
+
+mod check;
+mod layers;
+
+/// An error that may occur while loading the configuration.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// Failed to read the configuration file.
+    Read(PathBuf, io::Error),
+    /// The file is not a valid YAML or does not match the config schema. The
+    /// message contains the line and column of the error, if known.
+    Parse(PathBuf, serde_yaml::Error),
+    /// Failed to resolve the environment variable referenced in the file.
+    Env(String, String),
+    /// Failed to apply an override, either from the environment or a flag.
+    Override(String, String),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        match self {
+            ConfigError::Read(path, err) => write!(fmt, "failed to read `{}`: {}", path.display(), err),
+            ConfigError::Parse(path, err) => write!(fmt, "invalid config `{}`: {}", path.display(), err),
+            ConfigError::Env(name, err) => write!(fmt, "failed to interpolate `{}`: {}", name, err),
+            ConfigError::Override(source, err) => write!(fmt, "failed to apply `{}`: {}", source, err),
+        }
+    }
+}
+
+impl error::Error for ConfigError {}
+
+fn deserialize_addr<'de, D>(de: D) -> Result<SocketAddr, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let (addr, port) = Deserialize::deserialize(de)?;
+    let addr = SocketAddr::new(addr, port);
+
+    Ok(addr)
+}
+
+fn serialize_addr<S: Serializer>(addr: &SocketAddr, se: S) -> Result<S::Ok, S::Error> {
+    (addr.ip(), addr.port()).serialize(se)
+}
+
+fn deserialize_addr_opt<'de, D>(de: D) -> Result<Option<SocketAddr>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let addr: Option<(IpAddr, u16)> = Deserialize::deserialize(de)?;
+    Ok(addr.map(|(addr, port)| SocketAddr::new(addr, port)))
+}
+
+fn serialize_addr_opt<S: Serializer>(addr: &Option<SocketAddr>, se: S) -> Result<S::Ok, S::Error> {
+    addr.map(|v| (v.ip(), v.port())).serialize(se)
+}
+
+/// File modes are written in octal, like `"660"`, because YAML has no octal
+/// literals that survive all parsers.
+fn deserialize_mode<'de, D>(de: D) -> Result<u32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mode: String = Deserialize::deserialize(de)?;
+    u32::from_str_radix(&mode, 8).map_err(|_| de::Error::custom(format!("invalid file mode `{}`", mode)))
+}
+
+fn serialize_mode<S: Serializer>(mode: &u32, se: S) -> Result<S::Ok, S::Error> {
+    se.serialize_str(&format!("{:o}", mode))
+}
+
+fn deserialize_duration<'de, D>(de: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let secs = Deserialize::deserialize(de)?;
+    let duration = Duration::new(secs, 0);
+
+    Ok(duration)
+}
+
+fn serialize_duration<S: Serializer>(duration: &Duration, se: S) -> Result<S::Ok, S::Error> {
+    duration.as_secs().serialize(se)
+}
+
+fn parse_level<E: de::Error>(level: &str) -> Result<LevelFilter, E> {
+    level.parse().map_err(|_| E::custom(format!("invalid log level `{}`", level)))
+}
+
+fn deserialize_level<'de, D>(de: D) -> Result<LevelFilter, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let level: String = Deserialize::deserialize(de)?;
+    parse_level(&level)
+}
+
+fn serialize_level<S: Serializer>(level: &LevelFilter, se: S) -> Result<S::Ok, S::Error> {
+    se.serialize_str(&level.to_string().to_lowercase())
+}
+
+fn deserialize_levels<'de, D>(de: D) -> Result<BTreeMap<String, LevelFilter>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let levels: BTreeMap<String, String> = Deserialize::deserialize(de)?;
+    levels
+        .into_iter()
+        .map(|(module, level)| parse_level(&level).map(|level| (module, level)))
+        .collect()
+}
+
+fn serialize_levels<S: Serializer>(levels: &BTreeMap<String, LevelFilter>, se: S) -> Result<S::Ok, S::Error> {
+    se.collect_map(levels.iter().map(|(module, level)| (module, level.to_string().to_lowercase())))
+}
+
+/// Repository full name pattern, where `*` matches any sequence of
+/// characters except `/`, for example, `sonm-io/*`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Pattern(String);
+
+impl Pattern {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Pattern(v) => v,
+        }
+    }
+
+    pub fn is_wildcard(&self) -> bool {
+        self.as_str().contains('*')
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        Pattern::matches_bytes(self.as_str().as_bytes(), name.as_bytes())
+    }
+
+    fn matches_bytes(pattern: &[u8], name: &[u8]) -> bool {
+        match (pattern.split_first(), name.split_first()) {
+            (None, None) => true,
+            (Some((b'*', rest)), _) => {
+                Pattern::matches_bytes(rest, name) ||
+                    match name.split_first() {
+                        Some((c, tail)) if *c != b'/' => Pattern::matches_bytes(pattern, tail),
+                        _ => false,
+                    }
+            }
+            (Some((p, rest)), Some((c, tail))) if p.eq_ignore_ascii_case(c) => {
+                Pattern::matches_bytes(rest, tail)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Repositories served by the bot.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RepositoryConfig {
+    /// Full name of the repository, like `sonm-io/core`, or a pattern, like
+    /// `sonm-io/*` to serve the whole organization.
+    name: Pattern,
+    /// Repositories matched by `name`, but not served.
+    #[serde(default)]
+    exclude: Vec<Pattern>,
+    /// Overrides of the default policy.
+    #[serde(default)]
+    policy: PolicyOverrides,
+}
+
+impl RepositoryConfig {
+    pub fn name(&self) -> &Pattern {
+        &self.name
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        self.name.matches(name) && !self.exclude.iter().any(|v| v.matches(name))
+    }
+
+    pub fn policy(&self) -> &PolicyOverrides {
+        &self.policy
+    }
+}
+
+fn default_login() -> String {
+    "sonmbot".into()
+}
+
+fn default_status_enabled() -> bool {
+    true
+}
+
+fn default_checklist() -> bool {
+    true
+}
+
+/// Commit status the bot publishes on PR heads to explain why a PR is or
+/// is not merged.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct StatusConfig {
+    #[serde(default = "default_status_enabled")]
+    enabled: bool,
+    /// Status context, `<login>/merge` by default.
+    #[serde(default)]
+    context: Option<String>,
+    /// Template of the details link, where `{owner}`, `{repo}` and
+    /// `{number}` are substituted. Links to the PR itself by default.
+    #[serde(default)]
+    details_url: Option<String>,
+}
+
+impl StatusConfig {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns the status context of the bot with the given login.
+    pub fn context(&self, login: &str) -> String {
+        match &self.context {
+            Some(context) => context.clone(),
+            None => format!("{}/merge", login),
+        }
+    }
+
+    /// Returns the details link of the given PR.
+    pub fn details_url(&self, owner: &str, repo: &str, number: u64, url: &str) -> String {
+        match &self.details_url {
+            Some(template) => template
+                .replace("{owner}", owner)
+                .replace("{repo}", repo)
+                .replace("{number}", &number.to_string()),
+            None => url.into(),
+        }
+    }
+}
+
+impl Default for StatusConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_status_enabled(),
+            context: None,
+            details_url: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MergeBotConfig {
+    #[serde(deserialize_with = "deserialize_duration", serialize_with = "serialize_duration")]
+    interval: Duration,
+    /// Login the bot is mentioned by in PR comment commands.
+    #[serde(default = "default_login")]
+    login: String,
+    #[serde(default)]
+    status: StatusConfig,
+    /// Whether to keep a checklist of merge conditions in a single comment
+    /// on each PR.
+    #[serde(default = "default_checklist")]
+    checklist: bool,
+    /// Default policy for all repositories.
+    #[serde(default)]
+    policy: Policy,
+    repositories: Vec<RepositoryConfig>,
+}
+
+impl MergeBotConfig {
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn login(&self) -> &str {
+        &self.login
+    }
+
+    pub fn status(&self) -> &StatusConfig {
+        &self.status
+    }
+
+    pub fn checklist(&self) -> bool {
+        self.checklist
+    }
+
+    pub fn repositories(&self) -> &[RepositoryConfig] {
+        &self.repositories
+    }
+
+    /// Returns the first repository config matching the given full name.
+    pub fn repository(&self, name: &str) -> Option<&RepositoryConfig> {
+        self.repositories.iter().find(|v| v.matches(name))
+    }
+
+    /// Returns the effective policy for the given repository, or `None` if
+    /// the repository is not served.
+    pub fn policy(&self, name: &str) -> Option<Policy> {
+        self.repository(name).map(|v| v.policy.apply(&self.policy))
+    }
+}
+
+fn default_tls_reload_interval() -> Duration {
+    Duration::from_secs(60)
+}
+
+/// TLS settings of the HTTP listener.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TlsConfig {
+    /// Path to the PEM-encoded certificate chain.
+    cert: PathBuf,
+    /// Path to the PEM-encoded private key.
+    key: PathBuf,
+    /// Path to the PEM-encoded CA certificates, which client certificates
+    /// must be signed by. Enables mutual TLS.
+    #[serde(default)]
+    client_ca: Option<PathBuf>,
+    /// How often in seconds certificate files are checked for changes.
+    #[serde(
+        default = "default_tls_reload_interval",
+        deserialize_with = "deserialize_duration",
+        serialize_with = "serialize_duration"
+    )]
+    reload_interval: Duration,
+}
+
+impl TlsConfig {
+    pub fn cert(&self) -> &Path {
+        &self.cert
+    }
+
+    pub fn key(&self) -> &Path {
+        &self.key
+    }
+
+    pub fn client_ca(&self) -> Option<&Path> {
+        self.client_ca.as_ref().map(|v| v.as_path())
+    }
+
+    pub fn reload_interval(&self) -> Duration {
+        self.reload_interval
+    }
+}
+
+/// Groups of HTTP endpoints, which may be served by different listeners.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// The webhook endpoint, `POST /hook`.
+    Hook,
+    /// `/healthz`, `/readyz` and `/version`.
+    Health,
+    /// `/metrics`.
+    Metrics,
+    /// `/admin/*`, which is unauthenticated, so it is served only by
+    /// listeners listing it explicitly, preferably Unix sockets.
+    Admin,
+}
+
+fn default_scopes() -> Vec<Scope> {
+    vec![Scope::Hook, Scope::Health, Scope::Metrics]
+}
+
+fn default_socket_mode() -> u32 {
+    0o660
+}
+
+/// Address to listen on.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum ListenAddr {
+    Tcp {
+        #[serde(deserialize_with = "deserialize_addr", serialize_with = "serialize_addr")]
+        addr: SocketAddr,
+        /// Serve HTTPS instead of plain HTTP.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tls: Option<TlsConfig>,
+    },
+    /// Unix domain socket, which is created with the given permissions,
+    /// replacing a stale one.
+    Unix {
+        path: PathBuf,
+        #[serde(
+            default = "default_socket_mode",
+            deserialize_with = "deserialize_mode",
+            serialize_with = "serialize_mode"
+        )]
+        mode: u32,
+    },
+}
+
+impl Display for ListenAddr {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        match self {
+            ListenAddr::Tcp { addr, tls: None } => write!(fmt, "http://{}", addr),
+            ListenAddr::Tcp { addr, tls: Some(..) } => write!(fmt, "https://{}", addr),
+            ListenAddr::Unix { path, .. } => write!(fmt, "unix:{}", path.display()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ListenerConfig {
+    #[serde(flatten)]
+    addr: ListenAddr,
+    /// Endpoints served by this listener, all by default.
+    #[serde(default = "default_scopes")]
+    scopes: Vec<Scope>,
+}
+
+impl ListenerConfig {
+    pub fn addr(&self) -> &ListenAddr {
+        &self.addr
+    }
+
+    pub fn scopes(&self) -> &[Scope] {
+        &self.scopes
+    }
+
+    pub fn serves(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+}
+
+fn default_backlog() -> i32 {
+    1024
+}
+
+fn default_keep_alive() -> u64 {
+    5
+}
+
+fn default_client_timeout() -> u64 {
+    5
+}
+
+/// GitHub payloads may be up to 25MB.
+fn default_max_payload() -> usize {
+    25 * 1024 * 1024
+}
+
+fn default_shutdown_timeout() -> u16 {
+    30
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NetworkConfig {
+    /// Address of a single listener serving all endpoints, a shortcut for
+    /// `listeners` with one item.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_addr_opt",
+        serialize_with = "serialize_addr_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    addr: Option<SocketAddr>,
+    /// TLS settings of the `addr` listener.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tls: Option<TlsConfig>,
+    #[serde(default)]
+    listeners: Vec<ListenerConfig>,
+    /// Maximum number of pending connections.
+    #[serde(default = "default_backlog")]
+    backlog: i32,
+    /// Number of HTTP worker threads, the number of CPUs by default.
+    #[serde(default)]
+    workers: Option<usize>,
+    /// Keep-alive timeout in seconds, `0` disables keep-alive.
+    #[serde(default = "default_keep_alive")]
+    keep_alive: u64,
+    /// Time in seconds for a client to send request headers.
+    #[serde(default = "default_client_timeout")]
+    client_timeout: u64,
+    /// Maximum size of a request body in bytes.
+    #[serde(default = "default_max_payload")]
+    max_payload: usize,
+    /// Time in seconds to finish in-flight requests on shutdown.
+    #[serde(default = "default_shutdown_timeout")]
+    shutdown_timeout: u16,
+}
+
+impl NetworkConfig {
+    /// Returns all listeners, including the one specified by `addr`.
+    pub fn listeners(&self) -> Vec<ListenerConfig> {
+        let mut listeners = Vec::new();
+        if let Some(addr) = self.addr {
+            listeners.push(ListenerConfig {
+                addr: ListenAddr::Tcp { addr, tls: self.tls.clone() },
+                scopes: default_scopes(),
+            });
+        }
+
+        listeners.extend(self.listeners.iter().cloned());
+        listeners
+    }
+
+    pub fn backlog(&self) -> i32 {
+        self.backlog
+    }
+
+    pub fn workers(&self) -> Option<usize> {
+        self.workers
+    }
+
+    pub fn keep_alive(&self) -> Option<u64> {
+        match self.keep_alive {
+            0 => None,
+            v => Some(v),
+        }
+    }
+
+    pub fn client_timeout(&self) -> Duration {
+        Duration::from_secs(self.client_timeout)
+    }
+
+    pub fn max_payload(&self) -> usize {
+        self.max_payload
+    }
+
+    pub fn shutdown_timeout(&self) -> u16 {
+        self.shutdown_timeout
+    }
+}
+
+fn default_meta_refresh_interval() -> Duration {
+    Duration::from_secs(3600)
+}
+
+/// Source addresses the webhook endpoint accepts deliveries from.
+///
+/// Hooks are accepted from anywhere unless either ranges are given or GitHub
+/// ranges are enabled.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AllowlistConfig {
+    /// Allowed ranges in the CIDR notation.
+    #[serde(default)]
+    cidrs: Vec<Cidr>,
+    /// Whether to allow ranges GitHub delivers hooks from, as published by
+    /// its `/meta` API.
+    #[serde(default)]
+    github_meta: bool,
+    /// Optional path to the file where GitHub ranges are cached, so that
+    /// hooks are accepted right after restart.
+    #[serde(default)]
+    cache: Option<PathBuf>,
+    /// How often in seconds GitHub ranges are refreshed.
+    #[serde(
+        default = "default_meta_refresh_interval",
+        deserialize_with = "deserialize_duration",
+        serialize_with = "serialize_duration"
+    )]
+    refresh_interval: Duration,
+    /// Reverse proxies trusted to set `X-Forwarded-For`.
+    #[serde(default)]
+    trusted_proxies: Vec<Cidr>,
+}
+
+impl AllowlistConfig {
+    pub fn cidrs(&self) -> &[Cidr] {
+        &self.cidrs
+    }
+
+    pub fn github_meta(&self) -> bool {
+        self.github_meta
+    }
+
+    pub fn cache(&self) -> Option<&Path> {
+        self.cache.as_ref().map(|v| v.as_path())
+    }
+
+    pub fn refresh_interval(&self) -> Duration {
+        self.refresh_interval
+    }
+
+    pub fn trusted_proxies(&self) -> &[Cidr] {
+        &self.trusted_proxies
+    }
+}
+
+impl Default for AllowlistConfig {
+    fn default() -> Self {
+        Self {
+            cidrs: Vec::new(),
+            github_meta: false,
+            cache: None,
+            refresh_interval: default_meta_refresh_interval(),
+            trusted_proxies: Vec::new(),
+        }
+    }
+}
+
+/// Webhook endpoint settings.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HookConfig {
+    /// Secret shared with GitHub, used to verify payload signatures.
+    secret: Secret,
+    #[serde(default)]
+    allowlist: AllowlistConfig,
+}
+
+impl HookConfig {
+    pub fn secret(&self) -> &Secret {
+        &self.secret
+    }
+
+    pub fn allowlist(&self) -> &AllowlistConfig {
+        &self.allowlist
+    }
+}
+
+fn default_delivery_capacity() -> usize {
+    4096
+}
+
+/// Webhook deliveries deduplication settings.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DeliveryConfig {
+    /// Maximum number of recently seen delivery GUIDs to remember.
+    #[serde(default = "default_delivery_capacity")]
+    capacity: usize,
+    /// Optional path to the file where seen GUIDs are persisted across
+    /// restarts.
+    #[serde(default)]
+    path: Option<PathBuf>,
+}
+
+impl DeliveryConfig {
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_ref().map(|v| v.as_path())
+    }
+}
+
+impl Default for DeliveryConfig {
+    fn default() -> Self {
+        Self {
+            capacity: default_delivery_capacity(),
+            path: None,
+        }
+    }
+}
+
+/// Format of log lines.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable lines, colored when written into a terminal.
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+/// Destination of log lines.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogTarget {
+    Stdout,
+    /// Append into the file at the given path.
+    File(PathBuf),
+    /// Send into the syslog Unix datagram socket at the given path, usually
+    /// `/dev/log`.
+    Syslog(PathBuf),
+}
+
+fn default_log_format() -> LogFormat {
+    LogFormat::Text
+}
+
+fn default_log_level() -> LevelFilter {
+    LevelFilter::Debug
+}
+
+fn default_log_target() -> LogTarget {
+    LogTarget::Stdout
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LoggingConfig {
+    #[serde(default = "default_log_format")]
+    format: LogFormat,
+    /// Log level for the application itself.
+    #[serde(default = "default_log_level", deserialize_with = "deserialize_level", serialize_with = "serialize_level")]
+    level: LevelFilter,
+    /// Log levels for specific modules, for example `sonmhub::server` or
+    /// `actix_web`. Modules not listed here, except the application itself,
+    /// are not logged at all.
+    #[serde(default, deserialize_with = "deserialize_levels", serialize_with = "serialize_levels")]
+    modules: BTreeMap<String, LevelFilter>,
+    #[serde(default = "default_log_target")]
+    target: LogTarget,
+}
+
+impl LoggingConfig {
+    pub fn format(&self) -> LogFormat {
+        self.format
+    }
+
+    pub fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    pub fn modules(&self) -> &BTreeMap<String, LevelFilter> {
+        &self.modules
+    }
+
+    pub fn target(&self) -> &LogTarget {
+        &self.target
+    }
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            format: default_log_format(),
+            level: default_log_level(),
+            modules: BTreeMap::new(),
+            target: default_log_target(),
+        }
+    }
+}
+
+/// Application configuration.
+///
+/// It is layered: defaults are overridden by the YAML file, which may
+/// reference environment variables as `${NAME}`, then by `SONMHUB_*`
+/// environment variables and finally by `--set` flags.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Config {
+    network: NetworkConfig,
+    #[serde(default)]
+    logging: LoggingConfig,
+    hook: HookConfig,
+    #[serde(default)]
+    deliveries: DeliveryConfig,
+    github: GithubConfig,
+    merge: MergeBotConfig,
+    /// SHA-256 hash of the effective configuration.
+    #[serde(skip)]
+    hash: String,
+}
+
+impl Config {
+    /// Loads the configuration file, applying the given overrides.
+    pub fn load<P: AsRef<Path>>(path: P, overrides: &Overrides) -> Result<Config, ConfigError> {
+        let path = path.as_ref();
+        let content = Config::read(path)?;
+
+        Config::parse(path, &content, overrides)
+    }
+
+    fn read(path: &Path) -> Result<String, ConfigError> {
+        let mut content = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut content))
+            .map_err(|err| ConfigError::Read(path.into(), err))?;
+
+        Ok(content)
+    }
+
+    /// Builds the config tree with variables interpolated and overrides
+    /// applied.
+    fn tree(path: &Path, content: &str, overrides: &Overrides) -> Result<Value, ConfigError> {
+        let value = serde_yaml::from_str(content).map_err(|err| ConfigError::Parse(path.into(), err))?;
+        let value = Config::relocate(value).map_err(|err| ConfigError::Parse(path.into(), err))?;
+        overrides.apply(layers::interpolate(value)?)
+    }
+
+    /// Moves the `github` section from its former place under `merge` to the
+    /// top level, where it has to be unless the file is older than that.
+    fn relocate(value: Value) -> Result<Value, serde_yaml::Error> {
+        let mut root = match value {
+            Value::Mapping(root) => root,
+            value => return Ok(value),
+        };
+
+        let legacy = match root.get_mut(&Value::String("merge".into())) {
+            Some(Value::Mapping(merge)) => merge.remove(&Value::String("github".into())),
+            _ => None,
+        };
+
+        if let Some(legacy) = legacy {
+            let key = Value::String("github".into());
+            if root.contains_key(&key) {
+                return Err(de::Error::custom(
+                    "both `github` and the deprecated `merge.github` are set, remove the latter",
+                ));
+            }
+            root.insert(key, legacy);
+        }
+
+        Ok(Value::Mapping(root))
+    }
+
+    /// Returns `true` if the file sets the deprecated `merge.github` section.
+    fn is_legacy(content: &str) -> bool {
+        match serde_yaml::from_str::<Value>(content) {
+            Ok(Value::Mapping(root)) => match root.get(&Value::String("merge".into())) {
+                Some(Value::Mapping(merge)) => merge.contains_key(&Value::String("github".into())),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Returns the given error as reported for the original text, which
+    /// knows line and column numbers, if the text fails the same way.
+    ///
+    /// Errors caused by overrides or substituted variables are returned as
+    /// is, because they have no position in the file.
+    fn locate(content: &str, err: serde_yaml::Error) -> serde_yaml::Error {
+        if let Err(located) = serde_yaml::from_str::<Config>(content) {
+            if located.to_string().contains(&err.to_string()) {
+                return located;
+            }
+        }
+
+        err
+    }
+
+    fn parse(path: &Path, content: &str, overrides: &Overrides) -> Result<Config, ConfigError> {
+        let value = Config::tree(path, content, overrides)?;
+
+        let mut cfg = match Config::deserialize(layers::Coerce(value)) {
+            Ok(cfg) => cfg,
+            Err(err) => return Err(ConfigError::Parse(path.into(), Config::locate(content, err))),
+        };
+
+        // Secrets are redacted, so the published hash does not allow to guess
+        // them offline.
+        let effective = serde_yaml::to_string(&cfg).map_err(|err| ConfigError::Parse(path.into(), err))?;
+        let mut hasher = Sha256::new();
+        hasher.input_str(&effective);
+        cfg.hash = hasher.result_str();
+
+        Ok(cfg)
+    }
+
+    /// Returns all secrets from this config, which must never be logged.
+    pub fn secrets(&self) -> Vec<&str> {
+        let mut secrets = vec![self.hook.secret().expose()];
+        secrets.extend(self.github.secrets());
+        secrets
+    }
+
+    /// Returns the hex-encoded SHA-256 hash of the effective configuration,
+    /// which allows to distinguish instances running with different configs.
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    pub fn network(&self) -> &NetworkConfig {
+        &self.network
+    }
+
+    pub fn logging(&self) -> &LoggingConfig {
+        &self.logging
+    }
+
+    pub fn hook(&self) -> &HookConfig {
+        &self.hook
+    }
+
+    pub fn deliveries(&self) -> &DeliveryConfig {
+        &self.deliveries
+    }
+
+    pub fn github(&self) -> &GithubConfig {
+        &self.github
+    }
+
+    pub fn merge(&self) -> &MergeBotConfig {
+        &self.merge
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Pattern;
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        Pattern(pattern.into()).matches(name)
+    }
+
+    #[test]
+    fn pattern_matches_exact_name_case_insensitively() {
+        assert!(matches("sonm-io/core", "sonm-io/core"));
+        assert!(matches("sonm-io/core", "SONM-IO/Core"));
+        assert!(!matches("sonm-io/core", "sonm-io/core2"));
+        assert!(!matches("sonm-io/core", "sonm-io/cor"));
+    }
+
+    #[test]
+    fn pattern_wildcard_does_not_cross_slash() {
+        assert!(matches("sonm-io/*", "sonm-io/core"));
+        assert!(matches("*/core", "sonm-io/core"));
+        assert!(matches("*/*", "sonm-io/core"));
+        assert!(!matches("sonm-io/*", "other/core"));
+        assert!(!matches("*", "sonm-io/core"));
+        assert!(!matches("sonm-io/*", "sonm-io/core/extra"));
+    }
+
+    #[test]
+    fn pattern_wildcard_matches_any_sequence() {
+        assert!(matches("sonm-io/co*e", "sonm-io/core"));
+        assert!(matches("sonm-io/co*e", "sonm-io/coe"));
+        assert!(matches("sonm-io/*-bot", "sonm-io/merge-bot"));
+        assert!(!matches("sonm-io/*-bot", "sonm-io/merge-tool"));
+    }
+}
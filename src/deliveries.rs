@@ -0,0 +1,211 @@
+use std::collections::{HashSet, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use config::DeliveryConfig;
+use metrics;
+
+/// How many times the journal may outgrow the capacity before it is
+/// rewritten with the GUIDs that are still remembered.
+const JOURNAL_SLACK: usize = 2;
+
+#[derive(Debug)]
+struct Inner {
+    capacity: usize,
+    /// Delivery GUIDs ordered from the least to the most recently seen.
+    order: VecDeque<String>,
+    seen: HashSet<String>,
+    /// Optional append-only journal of seen GUIDs.
+    ///
+    /// Each line contains either a GUID or a GUID prefixed with `-`, which
+    /// means that it was forgotten.
+    journal: Option<(PathBuf, File)>,
+    /// Number of lines in the journal.
+    lines: usize,
+}
+
+impl Inner {
+    fn touch(&mut self, id: &str) {
+        if let Some(pos) = self.order.iter().position(|v| v == id) {
+            if let Some(id) = self.order.remove(pos) {
+                self.order.push_back(id);
+            }
+        }
+    }
+
+    fn push(&mut self, id: String) {
+        while self.order.len() >= self.capacity {
+            match self.order.pop_front() {
+                Some(id) => {
+                    self.seen.remove(&id);
+                }
+                None => break,
+            }
+        }
+
+        self.seen.insert(id.clone());
+        self.order.push_back(id);
+    }
+
+    fn append(&mut self, line: &str) {
+        if let Some((path, file)) = self.journal.as_mut() {
+            if let Err(err) = writeln!(file, "{}", line).and_then(|()| file.flush()) {
+                warn!("failed to persist delivery into `{}`: {}", path.display(), err);
+            }
+        }
+
+        self.lines += 1;
+        if self.lines >= self.capacity.saturating_mul(JOURNAL_SLACK) {
+            self.compact();
+        }
+    }
+
+    /// Rewrites the journal with the remembered GUIDs only.
+    fn compact(&mut self) {
+        let path = match self.journal.as_ref() {
+            Some((path, ..)) => path.clone(),
+            None => return,
+        };
+
+        let ids: Vec<&str> = self.order.iter().map(|v| v.as_str()).collect();
+        let result = Deliveries::rewrite(&path, &ids)
+            .and_then(|()| OpenOptions::new().append(true).open(&path));
+        match result {
+            Ok(file) => {
+                self.journal = Some((path, file));
+                self.lines = ids.len();
+            }
+            Err(err) => {
+                warn!("failed to compact deliveries journal `{}`: {}", path.display(), err);
+            }
+        }
+    }
+}
+
+/// Bounded LRU set of recently seen `X-GitHub-Delivery` GUIDs.
+///
+/// GitHub may deliver the same hook more than once, for example, when it is
+/// redelivered manually from the UI. Tracking GUIDs allows to acknowledge
+/// such hooks without processing them twice.
+#[derive(Clone, Debug)]
+pub struct Deliveries {
+    inner: Arc<Mutex<Inner>>,
+    duplicates: Arc<AtomicUsize>,
+}
+
+impl Deliveries {
+    /// Constructs a new deliveries set, restoring recently seen GUIDs from
+    /// the journal if it is configured.
+    pub fn new(cfg: &DeliveryConfig) -> Result<Self, io::Error> {
+        let mut inner = Inner {
+            capacity: cfg.capacity().max(1),
+            order: VecDeque::new(),
+            seen: HashSet::new(),
+            journal: None,
+            lines: 0,
+        };
+
+        if let Some(path) = cfg.path() {
+            for id in Deliveries::restore(path, inner.capacity)? {
+                inner.push(id);
+            }
+
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            inner.journal = Some((path.into(), file));
+            inner.lines = inner.order.len();
+        }
+
+        let deliveries = Self {
+            inner: Arc::new(Mutex::new(inner)),
+            duplicates: Arc::new(AtomicUsize::new(0)),
+        };
+
+        Ok(deliveries)
+    }
+
+    /// Reads the last `capacity` GUIDs from the journal, compacting it.
+    fn restore(path: &Path, capacity: usize) -> Result<Vec<String>, io::Error> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+
+        let mut ids = VecDeque::with_capacity(capacity);
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if line.starts_with('-') {
+                let id = &line[1..];
+                ids.retain(|v| v != id);
+                continue;
+            }
+
+            let id = line;
+            if ids.len() == capacity {
+                ids.pop_front();
+            }
+            ids.push_back(id.to_owned());
+        }
+
+        let ids: Vec<String> = ids.into_iter().collect();
+        Deliveries::rewrite(path, &ids)?;
+
+        Ok(ids)
+    }
+
+    /// Atomically replaces the journal with the given GUIDs.
+    fn rewrite<T: AsRef<str>>(path: &Path, ids: &[T]) -> Result<(), io::Error> {
+        let tmp = path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        for id in ids {
+            writeln!(file, "{}", id.as_ref())?;
+        }
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    }
+
+    /// Remembers the given delivery GUID.
+    ///
+    /// Returns `false` if it has been seen recently, i.e. the delivery is a
+    /// duplicate.
+    pub fn insert(&self, id: &str) -> bool {
+        let mut inner = self.inner.lock().expect("deliveries lock is poisoned");
+
+        if inner.seen.contains(id) {
+            inner.touch(id);
+            self.duplicates.fetch_add(1, Ordering::SeqCst);
+            metrics::WEBHOOK_DUPLICATES.inc();
+            return false;
+        }
+
+        inner.push(id.into());
+        inner.append(id);
+        true
+    }
+
+    /// Forgets the given delivery GUID, allowing it to be processed again.
+    ///
+    /// Used when a delivery was accepted, but failed to be enqueued, so that
+    /// its redelivery is not treated as a duplicate.
+    pub fn remove(&self, id: &str) {
+        let mut inner = self.inner.lock().expect("deliveries lock is poisoned");
+
+        if inner.seen.remove(id) {
+            inner.order.retain(|v| v != id);
+            inner.append(&format!("-{}", id));
+        }
+    }
+
+    /// Returns the number of duplicate deliveries seen so far.
+    pub fn duplicates(&self) -> usize {
+        self.duplicates.load(Ordering::SeqCst)
+    }
+}
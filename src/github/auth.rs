@@ -0,0 +1,379 @@
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use actix_web::client::ClientRequest;
+use actix_web::http::{Method, StatusCode};
+use actix_web::HttpMessage;
+use base64;
+use chrono::{DateTime, Duration, Utc};
+use futures::future::{self, Either};
+use futures::prelude::*;
+use jsonwebtoken::{self, Algorithm, Header};
+use serde::{de, Deserialize, Deserializer};
+use serde_json::Value;
+use url::Url;
+
+use github::{record, BaseUrl, Error, UserAgent};
+use logging;
+use secure::Secret;
+
+const MACHINE_MAN_PREVIEW: &str = "application/vnd.github.machine-man-preview+json";
+
+/// Lifetime of app JWTs. GitHub accepts at most 10 minutes.
+const JWT_TTL_SECS: i64 = 540;
+
+/// Installation tokens are refreshed when they expire sooner than this.
+const REFRESH_MARGIN_SECS: i64 = 300;
+
+/// GitHub App credentials.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AppConfig {
+    /// Application id, shown on the app settings page.
+    #[serde(deserialize_with = "deserialize_id")]
+    pub id: u64,
+    /// Path to the PEM-encoded RSA private key of the app.
+    pub private_key: PathBuf,
+}
+
+/// Credentials are buffered to find out their variant, so an id overridden
+/// by an environment variable or a flag arrives as a string.
+fn deserialize_id<'de, D: Deserializer<'de>>(de: D) -> Result<u64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Id {
+        Number(u64),
+        String(String),
+    }
+
+    match Id::deserialize(de)? {
+        Id::Number(id) => Ok(id),
+        Id::String(id) => id
+            .trim()
+            .parse()
+            .map_err(|_| de::Error::custom(format!("invalid app id `{}`", id))),
+    }
+}
+
+/// Credentials used to authenticate GitHub API requests.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Credentials {
+    /// Personal access token of the bot account.
+    Token { oauth_token: Secret },
+    /// GitHub App, authenticated as one of its installations.
+    App { app: AppConfig },
+}
+
+#[derive(Serialize)]
+struct Claims {
+    iat: i64,
+    exp: i64,
+    iss: u64,
+}
+
+struct App {
+    id: u64,
+    /// DER-encoded RSA private key.
+    key: Vec<u8>,
+    /// Installation ids by repository full name.
+    installations: Mutex<HashMap<String, u64>>,
+    /// Cached installation tokens with their expiration time.
+    tokens: Mutex<HashMap<u64, (Secret, DateTime<Utc>)>>,
+}
+
+impl App {
+    fn new(cfg: &AppConfig) -> Result<Self, Error> {
+        let mut pem = String::new();
+        File::open(&cfg.private_key)
+            .and_then(|mut file| file.read_to_string(&mut pem))
+            .map_err(Error::PrivateKey)?;
+
+        let app = Self {
+            id: cfg.id,
+            key: App::decode_pem(&pem)?,
+            installations: Mutex::new(HashMap::new()),
+            tokens: Mutex::new(HashMap::new()),
+        };
+
+        Ok(app)
+    }
+
+    /// Extracts DER-encoded key from the PEM armor.
+    fn decode_pem(pem: &str) -> Result<Vec<u8>, Error> {
+        let body: String = pem
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with("-----"))
+            .collect();
+
+        base64::decode(&body).map_err(|_| Error::InvalidPrivateKey)
+    }
+
+    /// Signs a short-living JWT, which authenticates requests as the app
+    /// itself.
+    fn jwt(&self) -> Result<String, Error> {
+        let now = Utc::now().timestamp();
+        let claims = Claims {
+            // Allow some clock drift.
+            iat: now - 60,
+            exp: now + JWT_TTL_SECS,
+            iss: self.id,
+        };
+
+        let jwt = jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &self.key)?;
+        Ok(jwt)
+    }
+
+    fn installation(&self, repository: &str) -> Option<u64> {
+        self.installations.lock().expect("installations lock is poisoned").get(repository).cloned()
+    }
+
+    /// Returns a cached token for the given installation unless it is about
+    /// to expire.
+    fn token(&self, installation: u64) -> Option<Secret> {
+        let deadline = Utc::now() + Duration::seconds(REFRESH_MARGIN_SECS);
+
+        match self.tokens.lock().expect("tokens lock is poisoned").get(&installation) {
+            Some((token, expires_at)) if *expires_at > deadline => Some(token.clone()),
+            Some(..) | None => None,
+        }
+    }
+}
+
+impl Debug for App {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        // Never print the key.
+        fmt.debug_struct("App").field("id", &self.id).finish()
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Kind {
+    Token(Secret),
+    App(Arc<App>),
+}
+
+/// Provides values of the `Authorization` header for GitHub API requests.
+///
+/// With a personal token this is trivial. A GitHub App instead exchanges a
+/// signed JWT for per-installation access tokens, which are cached until
+/// they are about to expire. The installation is picked by the repository
+/// the request is made for, learned from incoming webhooks or resolved via
+/// the API.
+#[derive(Clone, Debug)]
+pub struct Authenticator {
+    base_url: BaseUrl,
+    user_agent: UserAgent,
+    kind: Kind,
+}
+
+impl Authenticator {
+    pub fn new(base_url: BaseUrl, user_agent: UserAgent, credentials: &Credentials) -> Result<Self, Error> {
+        let kind = match credentials {
+            Credentials::Token { oauth_token } => Kind::Token(oauth_token.clone()),
+            Credentials::App { app } => Kind::App(Arc::new(App::new(app)?)),
+        };
+
+        Ok(Self { base_url, user_agent, kind })
+    }
+
+    /// Remembers the app installation for the given repository.
+    pub fn register(&self, repository: &str, installation: u64) {
+        if let Kind::App(app) = &self.kind {
+            let mut installations = app.installations.lock().expect("installations lock is poisoned");
+            if installations.insert(repository.into(), installation) != Some(installation) {
+                debug!("repository {} is served by installation {}", repository, installation);
+            }
+        }
+    }
+
+    /// Learns the app installation from the webhook payload, if any.
+    pub fn observe(&self, payload: &Value) {
+        let repository = payload["repository"]["full_name"].as_str();
+        let installation = payload["installation"]["id"].as_u64();
+
+        if let (Some(repository), Some(installation)) = (repository, installation) {
+            self.register(repository, installation);
+        }
+    }
+
+    /// Returns the `Authorization` header value for a request made on behalf
+    /// of the given repository, specified by its full name.
+    ///
+    /// Without a repository an app authenticates with any of its installation
+    /// tokens, so that requests like the rate limit probe are made on behalf
+    /// of an installation rather than the app itself. The JWT is used only if
+    /// the app has no installations at all.
+    pub fn authorization(&self, repository: Option<String>) -> impl Future<Item = String, Error = Error> {
+        match &self.kind {
+            Kind::Token(token) => Either::A(future::ok(format!("token {}", token.expose()))),
+            Kind::App(app) => Either::B(Authenticator::installation_authorization(
+                self.clone(),
+                app.clone(),
+                repository,
+            )),
+        }
+    }
+
+    #[async]
+    fn installation_authorization(self, app: Arc<App>, repository: Option<String>) -> Result<String, Error> {
+        let installation = match repository {
+            Some(repository) => match app.installation(&repository) {
+                Some(installation) => installation,
+                None => {
+                    let installation = await!(self.clone().resolve(app.clone(), repository.clone()))?;
+                    self.register(&repository, installation);
+                    installation
+                }
+            },
+            None => {
+                let installation = app.tokens
+                    .lock()
+                    .expect("tokens lock is poisoned")
+                    .keys()
+                    .next()
+                    .cloned()
+                    .or_else(|| {
+                        let installations = app.installations.lock().expect("installations lock is poisoned");
+                        installations.values().next().cloned()
+                    });
+
+                let installation = match installation {
+                    Some(installation) => Some(installation),
+                    None => await!(self.clone().any_installation(app.clone()))?,
+                };
+
+                match installation {
+                    Some(installation) => installation,
+                    None => return Ok(format!("Bearer {}", app.jwt()?)),
+                }
+            }
+        };
+
+        if let Some(token) = app.token(installation) {
+            return Ok(format!("token {}", token.expose()));
+        }
+
+        let (token, expires_at) = await!(self.clone().exchange(app.clone(), installation))?;
+        logging::register_secret(token.expose());
+        debug!("refreshed token for installation {}, expires at {}", installation, expires_at);
+
+        let authorization = format!("token {}", token.expose());
+        app.tokens.lock().expect("tokens lock is poisoned").insert(installation, (token, expires_at));
+
+        Ok(authorization)
+    }
+
+    fn request(&self, app: &App, method: Method, path: &str) -> Result<ClientRequest, Error> {
+        let request = ClientRequest::build()
+            .method(method)
+            .uri(url(self.base_url.clone(), path))
+            .header("Accept", MACHINE_MAN_PREVIEW)
+            .header("Authorization", format!("Bearer {}", app.jwt()?))
+            .header("User-Agent", self.user_agent.clone())
+            .finish()?;
+
+        Ok(request)
+    }
+
+    /// Finds the app installation for the given repository.
+    #[async]
+    fn resolve(self, app: Arc<App>, repository: String) -> Result<u64, Error> {
+        let path = format!("repos/{}/installation", repository);
+        let request = self.request(&app, Method::GET, &path)?;
+
+        debug!("-> {} {}", request.method(), request.uri());
+        let response = await!(request.send().then(Ok::<_, Error>))?;
+        record("installation", &response);
+        let response = response?;
+        debug!("<- {}", response.status());
+
+        match response.status() {
+            StatusCode::OK => {
+                let value: Value = await!(response.json())?;
+                value["id"].as_u64().ok_or(Error::Unexpected(StatusCode::OK))
+            }
+            StatusCode::NOT_FOUND => Err(Error::InstallationNotFound(repository)),
+            status => Err(Error::Unexpected(status)),
+        }
+    }
+
+    /// Returns the id of any installation of the app, if it has one.
+    #[async]
+    fn any_installation(self, app: Arc<App>) -> Result<Option<u64>, Error> {
+        let request = self.request(&app, Method::GET, "app/installations?per_page=1")?;
+
+        debug!("-> {} {}", request.method(), request.uri());
+        let response = await!(request.send().then(Ok::<_, Error>))?;
+        record("app_installations", &response);
+        let response = response?;
+        debug!("<- {}", response.status());
+
+        match response.status() {
+            StatusCode::OK => {
+                let value: Value = await!(response.json())?;
+                Ok(value[0]["id"].as_u64())
+            }
+            status => Err(Error::Unexpected(status)),
+        }
+    }
+
+    /// Exchanges the app JWT for an installation access token.
+    #[async]
+    fn exchange(self, app: Arc<App>, installation: u64) -> Result<(Secret, DateTime<Utc>), Error> {
+        let path = format!("app/installations/{}/access_tokens", installation);
+        let request = self.request(&app, Method::POST, &path)?;
+
+        debug!("-> {} {}", request.method(), request.uri());
+        let response = await!(request.send().then(Ok::<_, Error>))?;
+        record("installations_access_tokens", &response);
+        let response = response?;
+        debug!("<- {}", response.status());
+
+        match response.status() {
+            StatusCode::CREATED => {
+                let value: Value = await!(response.json())?;
+
+                let token = value["token"].as_str().ok_or(Error::Unexpected(StatusCode::CREATED))?;
+                let expires_at = value["expires_at"]
+                    .as_str()
+                    .and_then(|v| DateTime::parse_from_rfc3339(v).ok())
+                    .ok_or(Error::Unexpected(StatusCode::CREATED))?;
+
+                Ok((Secret::new(token), expires_at.with_timezone(&Utc)))
+            }
+            status => Err(Error::Unexpected(status)),
+        }
+    }
+}
+
+/// Joins the base URL with the given path, which may contain a query.
+fn url(base_url: BaseUrl, path: &str) -> Url {
+    let mut parts = path.splitn(2, '?');
+    let mut url = base_url.with_path(parts.next().unwrap_or(""));
+    url.set_query(parts.next());
+    url
+}
+
+#[cfg(test)]
+mod test {
+    use github::BaseUrl;
+
+    use super::url;
+
+    #[test]
+    fn keeps_query_out_of_path() {
+        let cases = [
+            ("app/installations?per_page=1", "https://api.github.com/app/installations?per_page=1"),
+            ("repos/foo/bar/installation", "https://api.github.com/repos/foo/bar/installation"),
+        ];
+
+        for &(path, expected) in &cases {
+            assert_eq!(url(BaseUrl::default(), path).as_str(), expected, "{}", path);
+        }
+    }
+}
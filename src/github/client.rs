@@ -1,15 +1,33 @@
 use std::convert::TryFrom;
+use std::io;
 
-use actix_web::client::ClientRequest;
-use actix_web::client::SendRequestError;
+use actix_web::client::{ClientRequest, ClientResponse, SendRequestError};
 use actix_web::error::JsonPayloadError;
 use actix_web::http::{Method, StatusCode};
 use actix_web::{self, HttpMessage};
 use futures::prelude::*;
+use jsonwebtoken;
 use serde_json::{self, Value};
 use url::{self, Url};
 
-use github::{BaseUrl, CombinedStatus, UserAgent};
+use github::{
+    Actor, Authenticator, BaseUrl, ChangedFile, CombinedStatus, Comment, Comparison, Contents, Credentials, MergeableState, Meta, Permission,
+    PullRequest, PullRequestPage, RateLimit, Reaction, StatusInput, UserAgent,
+};
+use metrics;
+use secure::RedactedHeaders;
+
+/// Maximum number of PR files GitHub lists, so longer lists are truncated.
+pub const MAX_FILES: usize = 3000;
+
+/// Page size of PR files.
+const FILES_PER_PAGE: usize = 100;
+
+/// Page size of PR conversation comments.
+const COMMENTS_PER_PAGE: usize = 100;
+
+/// Page size of team members.
+const MEMBERS_PER_PAGE: usize = 100;
 
 #[derive(Debug)]
 pub enum Error {
@@ -18,6 +36,17 @@ pub enum Error {
     Web(actix_web::Error),
     SendRequest(SendRequestError),
     JsonPayload(JsonPayloadError),
+    /// Failed to read the app private key.
+    PrivateKey(io::Error),
+    /// The app private key is not a PEM-encoded RSA key.
+    InvalidPrivateKey,
+    Jwt(jsonwebtoken::errors::Error),
+    /// The app is not installed for the given repository.
+    InstallationNotFound(String),
+    /// GitHub responded with an unexpected status or payload.
+    Unexpected(StatusCode),
+    /// GraphQL query failed, contains the `errors` field of the response.
+    GraphQl(Value),
 }
 
 impl From<url::ParseError> for Error {
@@ -50,14 +79,22 @@ impl From<JsonPayloadError> for Error {
     }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+impl From<jsonwebtoken::errors::Error> for Error {
+    fn from(err: jsonwebtoken::errors::Error) -> Self {
+        Error::Jwt(err)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Config {
     /// GitHub base URL.
     #[serde(default)]
     pub base_url: BaseUrl,
     #[serde(default)]
     pub user_agent: UserAgent,
-    pub oauth_token: String,
+    /// Either `oauth_token` or `app` section.
+    #[serde(flatten)]
+    pub credentials: Credentials,
 }
 
 impl Config {
@@ -66,16 +103,56 @@ impl Config {
         "application/vnd.github.v3+json"
     }
 
-    #[inline]
-    pub fn authorization(&self) -> String {
-        format!("token {}", self.oauth_token)
+    /// Returns secrets specified directly in this config.
+    pub fn secrets(&self) -> Vec<&str> {
+        match &self.credentials {
+            Credentials::Token { oauth_token } => vec![oauth_token.expose()],
+            Credentials::App { .. } => Vec::new(),
+        }
+    }
+}
+
+/// Records metrics of a completed GitHub API call.
+pub fn record(endpoint: &str, response: &Result<ClientResponse, SendRequestError>) {
+    match response {
+        Ok(response) => {
+            metrics::GITHUB_API_CALLS
+                .with_label_values(&[endpoint, response.status().as_str()])
+                .inc();
+
+            let remaining = response.headers()
+                .get("X-RateLimit-Remaining")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse().ok());
+
+            if let Some(remaining) = remaining {
+                metrics::GITHUB_RATE_LIMIT_REMAINING.set(remaining);
+            }
+        }
+        Err(..) => {
+            metrics::GITHUB_API_CALLS.with_label_values(&[endpoint, "error"]).inc();
+        }
     }
 }
 
 trait Request {
     fn method() -> Method;
+    /// Returns the endpoint name, used as a metrics label.
+    fn endpoint() -> &'static str;
+
+    /// Returns the media type to accept, which enables API previews.
+    fn accept() -> &'static str {
+        "application/vnd.github.v3+json"
+    }
+
     fn path(&self) -> String;
     fn body(&self) -> Option<Result<String, Error>>;
+
+    /// Returns the full name of the repository this request is made for,
+    /// which determines the credentials to use.
+    fn repository(&self) -> Option<String> {
+        None
+    }
 }
 
 pub struct MergeRequest {
@@ -96,6 +173,10 @@ impl Request for MergeRequest {
         Method::POST
     }
 
+    fn endpoint() -> &'static str {
+        "merges"
+    }
+
     fn path(&self) -> String {
         format!("repos/{}/{}/merges", self.owner, self.repo)
     }
@@ -103,16 +184,797 @@ impl Request for MergeRequest {
     fn body(&self) -> Option<Result<String, Error>> {
         Some(serde_json::to_string(&self.body).map_err(Error::Json))
     }
+
+    fn repository(&self) -> Option<String> {
+        Some(format!("{}/{}", self.owner, self.repo))
+    }
+}
+
+pub struct RateLimitRequest;
+
+impl Request for RateLimitRequest {
+    fn method() -> Method {
+        Method::GET
+    }
+
+    fn endpoint() -> &'static str {
+        "rate_limit"
+    }
+
+    fn path(&self) -> String {
+        "rate_limit".into()
+    }
+
+    fn body(&self) -> Option<Result<String, Error>> {
+        None
+    }
+}
+
+pub struct MetaRequest;
+
+impl Request for MetaRequest {
+    fn method() -> Method {
+        Method::GET
+    }
+
+    fn endpoint() -> &'static str {
+        "meta"
+    }
+
+    fn path(&self) -> String {
+        "meta".into()
+    }
+
+    fn body(&self) -> Option<Result<String, Error>> {
+        None
+    }
+}
+
+struct PermissionRequest {
+    owner: String,
+    repo: String,
+    user: String,
+}
+
+impl Request for PermissionRequest {
+    fn method() -> Method {
+        Method::GET
+    }
+
+    fn endpoint() -> &'static str {
+        "collaborators_permission"
+    }
+
+    fn path(&self) -> String {
+        format!("repos/{}/{}/collaborators/{}/permission", self.owner, self.repo, self.user)
+    }
+
+    fn body(&self) -> Option<Result<String, Error>> {
+        None
+    }
+
+    fn repository(&self) -> Option<String> {
+        Some(format!("{}/{}", self.owner, self.repo))
+    }
+}
+
+struct CreateCommentRequest {
+    owner: String,
+    repo: String,
+    number: u64,
+    body: String,
+}
+
+impl Request for CreateCommentRequest {
+    fn method() -> Method {
+        Method::POST
+    }
+
+    fn endpoint() -> &'static str {
+        "issues_comments"
+    }
+
+    fn path(&self) -> String {
+        format!("repos/{}/{}/issues/{}/comments", self.owner, self.repo, self.number)
+    }
+
+    fn body(&self) -> Option<Result<String, Error>> {
+        Some(serde_json::to_string(&json!({"body": self.body})).map_err(Error::Json))
+    }
+
+    fn repository(&self) -> Option<String> {
+        Some(format!("{}/{}", self.owner, self.repo))
+    }
+}
+
+struct ListCommentsRequest {
+    owner: String,
+    repo: String,
+    number: u64,
+    /// One-based page number.
+    page: usize,
+}
+
+impl Request for ListCommentsRequest {
+    fn method() -> Method {
+        Method::GET
+    }
+
+    fn endpoint() -> &'static str {
+        "issues_comments"
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "repos/{}/{}/issues/{}/comments?per_page={}&page={}",
+            self.owner, self.repo, self.number, COMMENTS_PER_PAGE, self.page
+        )
+    }
+
+    fn body(&self) -> Option<Result<String, Error>> {
+        None
+    }
+
+    fn repository(&self) -> Option<String> {
+        Some(format!("{}/{}", self.owner, self.repo))
+    }
+}
+
+struct EditCommentRequest {
+    owner: String,
+    repo: String,
+    comment: u64,
+    body: String,
+}
+
+impl Request for EditCommentRequest {
+    fn method() -> Method {
+        Method::PATCH
+    }
+
+    fn endpoint() -> &'static str {
+        "issues_comments_edit"
+    }
+
+    fn path(&self) -> String {
+        format!("repos/{}/{}/issues/comments/{}", self.owner, self.repo, self.comment)
+    }
+
+    fn body(&self) -> Option<Result<String, Error>> {
+        Some(serde_json::to_string(&json!({"body": self.body})).map_err(Error::Json))
+    }
+
+    fn repository(&self) -> Option<String> {
+        Some(format!("{}/{}", self.owner, self.repo))
+    }
+}
+
+struct CreateReactionRequest {
+    owner: String,
+    repo: String,
+    comment: u64,
+    content: Reaction,
+}
+
+impl Request for CreateReactionRequest {
+    fn method() -> Method {
+        Method::POST
+    }
+
+    fn endpoint() -> &'static str {
+        "comments_reactions"
+    }
+
+    /// Reactions are still in preview.
+    fn accept() -> &'static str {
+        "application/vnd.github.squirrel-girl-preview+json"
+    }
+
+    fn path(&self) -> String {
+        format!("repos/{}/{}/issues/comments/{}/reactions", self.owner, self.repo, self.comment)
+    }
+
+    fn body(&self) -> Option<Result<String, Error>> {
+        Some(serde_json::to_string(&json!({"content": self.content})).map_err(Error::Json))
+    }
+
+    fn repository(&self) -> Option<String> {
+        Some(format!("{}/{}", self.owner, self.repo))
+    }
+}
+
+struct CompareRequest {
+    owner: String,
+    repo: String,
+    base: String,
+    head: String,
+}
+
+impl Request for CompareRequest {
+    fn method() -> Method {
+        Method::GET
+    }
+
+    fn endpoint() -> &'static str {
+        "compare"
+    }
+
+    fn path(&self) -> String {
+        format!("repos/{}/{}/compare/{}...{}", self.owner, self.repo, self.base, self.head)
+    }
+
+    fn body(&self) -> Option<Result<String, Error>> {
+        None
+    }
+
+    fn repository(&self) -> Option<String> {
+        Some(format!("{}/{}", self.owner, self.repo))
+    }
+}
+
+struct ContentsRequest {
+    owner: String,
+    repo: String,
+    path: String,
+    reference: String,
+}
+
+impl Request for ContentsRequest {
+    fn method() -> Method {
+        Method::GET
+    }
+
+    fn endpoint() -> &'static str {
+        "contents"
+    }
+
+    fn path(&self) -> String {
+        format!("repos/{}/{}/contents/{}?ref={}", self.owner, self.repo, self.path, self.reference)
+    }
+
+    fn body(&self) -> Option<Result<String, Error>> {
+        None
+    }
+
+    fn repository(&self) -> Option<String> {
+        Some(format!("{}/{}", self.owner, self.repo))
+    }
+}
+
+struct TeamMembersRequest {
+    owner: String,
+    repo: String,
+    org: String,
+    team: String,
+    page: u32,
+}
+
+impl Request for TeamMembersRequest {
+    fn method() -> Method {
+        Method::GET
+    }
+
+    fn endpoint() -> &'static str {
+        "teams_members"
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "orgs/{}/teams/{}/members?per_page={}&page={}",
+            self.org, self.team, MEMBERS_PER_PAGE, self.page
+        )
+    }
+
+    fn body(&self) -> Option<Result<String, Error>> {
+        None
+    }
+
+    /// Teams are resolved with the credentials of the repository they own.
+    fn repository(&self) -> Option<String> {
+        Some(format!("{}/{}", self.owner, self.repo))
+    }
+}
+
+struct ListFilesRequest {
+    owner: String,
+    repo: String,
+    number: u64,
+    page: u32,
+}
+
+impl Request for ListFilesRequest {
+    fn method() -> Method {
+        Method::GET
+    }
+
+    fn endpoint() -> &'static str {
+        "pulls_files"
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "repos/{}/{}/pulls/{}/files?per_page={}&page={}",
+            self.owner, self.repo, self.number, FILES_PER_PAGE, self.page
+        )
+    }
+
+    fn body(&self) -> Option<Result<String, Error>> {
+        None
+    }
+
+    fn repository(&self) -> Option<String> {
+        Some(format!("{}/{}", self.owner, self.repo))
+    }
+}
+
+struct AddLabelsRequest {
+    owner: String,
+    repo: String,
+    number: u64,
+    labels: Vec<String>,
+}
+
+impl Request for AddLabelsRequest {
+    fn method() -> Method {
+        Method::POST
+    }
+
+    fn endpoint() -> &'static str {
+        "issues_labels"
+    }
+
+    fn path(&self) -> String {
+        format!("repos/{}/{}/issues/{}/labels", self.owner, self.repo, self.number)
+    }
+
+    fn body(&self) -> Option<Result<String, Error>> {
+        Some(serde_json::to_string(&self.labels).map_err(Error::Json))
+    }
+
+    fn repository(&self) -> Option<String> {
+        Some(format!("{}/{}", self.owner, self.repo))
+    }
+}
+
+struct RequestReviewersRequest {
+    owner: String,
+    repo: String,
+    number: u64,
+    reviewers: Vec<String>,
+}
+
+impl Request for RequestReviewersRequest {
+    fn method() -> Method {
+        Method::POST
+    }
+
+    fn endpoint() -> &'static str {
+        "pulls_requested_reviewers"
+    }
+
+    fn path(&self) -> String {
+        format!("repos/{}/{}/pulls/{}/requested_reviewers", self.owner, self.repo, self.number)
+    }
+
+    fn body(&self) -> Option<Result<String, Error>> {
+        Some(serde_json::to_string(&json!({"reviewers": self.reviewers})).map_err(Error::Json))
+    }
+
+    fn repository(&self) -> Option<String> {
+        Some(format!("{}/{}", self.owner, self.repo))
+    }
+}
+
+struct RemoveLabelRequest {
+    owner: String,
+    repo: String,
+    number: u64,
+    label: String,
+}
+
+impl Request for RemoveLabelRequest {
+    fn method() -> Method {
+        Method::DELETE
+    }
+
+    fn endpoint() -> &'static str {
+        "issues_labels_remove"
+    }
+
+    fn path(&self) -> String {
+        format!("repos/{}/{}/issues/{}/labels/{}", self.owner, self.repo, self.number, self.label)
+    }
+
+    fn body(&self) -> Option<Result<String, Error>> {
+        None
+    }
+
+    fn repository(&self) -> Option<String> {
+        Some(format!("{}/{}", self.owner, self.repo))
+    }
+}
+
+struct GetPullRequestRequest {
+    owner: String,
+    repo: String,
+    number: u64,
+}
+
+impl Request for GetPullRequestRequest {
+    fn method() -> Method {
+        Method::GET
+    }
+
+    fn endpoint() -> &'static str {
+        "pulls"
+    }
+
+    fn path(&self) -> String {
+        format!("repos/{}/{}/pulls/{}", self.owner, self.repo, self.number)
+    }
+
+    fn body(&self) -> Option<Result<String, Error>> {
+        None
+    }
+
+    fn repository(&self) -> Option<String> {
+        Some(format!("{}/{}", self.owner, self.repo))
+    }
+}
+
+struct DismissReviewRequest {
+    owner: String,
+    repo: String,
+    number: u64,
+    review: u64,
+    message: String,
+}
+
+impl Request for DismissReviewRequest {
+    fn method() -> Method {
+        Method::PUT
+    }
+
+    fn endpoint() -> &'static str {
+        "pulls_reviews_dismissals"
+    }
+
+    fn path(&self) -> String {
+        format!(
+            "repos/{}/{}/pulls/{}/reviews/{}/dismissals",
+            self.owner, self.repo, self.number, self.review
+        )
+    }
+
+    fn body(&self) -> Option<Result<String, Error>> {
+        Some(serde_json::to_string(&json!({"message": self.message})).map_err(Error::Json))
+    }
+
+    fn repository(&self) -> Option<String> {
+        Some(format!("{}/{}", self.owner, self.repo))
+    }
+}
+
+struct CreateStatusRequest {
+    owner: String,
+    repo: String,
+    sha: String,
+    body: StatusInput,
+}
+
+impl Request for CreateStatusRequest {
+    fn method() -> Method {
+        Method::POST
+    }
+
+    fn endpoint() -> &'static str {
+        "statuses"
+    }
+
+    fn path(&self) -> String {
+        format!("repos/{}/{}/statuses/{}", self.owner, self.repo, self.sha)
+    }
+
+    fn body(&self) -> Option<Result<String, Error>> {
+        Some(serde_json::to_string(&self.body).map_err(Error::Json))
+    }
+
+    fn repository(&self) -> Option<String> {
+        Some(format!("{}/{}", self.owner, self.repo))
+    }
+}
+
+struct GraphQlRequest {
+    repository: String,
+    query: &'static str,
+    variables: Value,
+}
+
+impl Request for GraphQlRequest {
+    fn method() -> Method {
+        Method::POST
+    }
+
+    fn endpoint() -> &'static str {
+        "graphql"
+    }
+
+    fn path(&self) -> String {
+        "graphql".into()
+    }
+
+    fn body(&self) -> Option<Result<String, Error>> {
+        let body = json!({
+            "query": self.query,
+            "variables": self.variables,
+        });
+
+        Some(serde_json::to_string(&body).map_err(Error::Json))
+    }
+
+    fn repository(&self) -> Option<String> {
+        Some(self.repository.clone())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MergePullRequestInput {
+    /// Expected head SHA, the merge fails if the head has moved.
+    pub sha: String,
+    pub merge_method: &'static str,
+}
+
+struct MergePullRequestRequest {
+    owner: String,
+    repo: String,
+    number: u64,
+    body: MergePullRequestInput,
+}
+
+impl Request for MergePullRequestRequest {
+    fn method() -> Method {
+        Method::PUT
+    }
+
+    fn endpoint() -> &'static str {
+        "pulls_merge"
+    }
+
+    fn path(&self) -> String {
+        format!("repos/{}/{}/pulls/{}/merge", self.owner, self.repo, self.number)
+    }
+
+    fn body(&self) -> Option<Result<String, Error>> {
+        Some(serde_json::to_string(&self.body).map_err(Error::Json))
+    }
+
+    fn repository(&self) -> Option<String> {
+        Some(format!("{}/{}", self.owner, self.repo))
+    }
 }
 
 #[derive(Clone)]
 pub struct PullRequestApi {
     cfg: Config,
+    auth: Authenticator,
     owner: String,
     repo: String,
 }
 
 impl PullRequestApi {
+    /// Returns all open pull requests, oldest first.
+    pub fn list(&self) -> impl Future<Item = Vec<PullRequest>, Error = Error> {
+        PullRequestApi::list_pages(self.clone())
+    }
+
+    #[async]
+    fn list_pages(self) -> Result<Vec<PullRequest>, Error> {
+        let mut result = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let request = GraphQlRequest {
+                repository: format!("{}/{}", self.owner, self.repo),
+                query: include_str!("../../graphql/pull_requests.graphql"),
+                variables: json!({
+                    "owner": self.owner,
+                    "name": self.repo,
+                    "cursor": cursor,
+                }),
+            };
+
+            let value = await!(Client::execute(self.cfg.clone(), self.auth.clone(), request))?;
+            if !value["errors"].is_null() {
+                return Err(Error::GraphQl(value["errors"].clone()));
+            }
+
+            let page: PullRequestPage = serde_json::from_value(value["data"]["repository"]["pullRequests"].clone())?;
+            result.extend(page.nodes);
+            if !page.page_info.has_next_page || page.page_info.end_cursor.is_none() {
+                break;
+            }
+            cursor = page.page_info.end_cursor;
+        }
+
+        Ok(result)
+    }
+
+    /// Returns the detailed mergeability of the PR.
+    ///
+    /// Requesting a single PR makes GitHub compute its mergeability, which
+    /// is `Unknown` until then.
+    pub fn mergeable(&self, number: u64) -> impl Future<Item = MergeableState, Error = Error> {
+        let request = GetPullRequestRequest {
+            owner: self.owner.clone(),
+            repo: self.repo.clone(),
+            number,
+        };
+
+        Client::execute(self.cfg.clone(), self.auth.clone(), request)
+            .map(|value| MergeableState::from_rest(value["mergeable"].as_bool(), value["mergeable_state"].as_str()))
+    }
+
+    /// Merges the pull request, given that its head is still at `sha`.
+    pub fn merge(&self, number: u64, sha: &str) -> impl Future<Item = Value, Error = Error> {
+        let request = MergePullRequestRequest {
+            owner: self.owner.clone(),
+            repo: self.repo.clone(),
+            number,
+            body: MergePullRequestInput {
+                sha: sha.into(),
+                merge_method: "merge",
+            },
+        };
+
+        Client::execute(self.cfg.clone(), self.auth.clone(), request)
+    }
+
+    /// Posts a new comment to the PR conversation.
+    pub fn comment(&self, number: u64, body: &str) -> impl Future<Item = Comment, Error = Error> {
+        let request = CreateCommentRequest {
+            owner: self.owner.clone(),
+            repo: self.repo.clone(),
+            number,
+            body: body.into(),
+        };
+
+        Client::execute(self.cfg.clone(), self.auth.clone(), request)
+            .and_then(|value| serde_json::from_value(value).map_err(Error::Json))
+    }
+
+    /// Returns all conversation comments of the PR, oldest first.
+    #[async]
+    pub fn comments(self, number: u64) -> Result<Vec<Comment>, Error> {
+        let mut result = Vec::new();
+        for page in 1.. {
+            let request = ListCommentsRequest {
+                owner: self.owner.clone(),
+                repo: self.repo.clone(),
+                number,
+                page,
+            };
+
+            let value = await!(Client::execute(self.cfg.clone(), self.auth.clone(), request))?;
+            let comments: Vec<Comment> = serde_json::from_value(value)?;
+            let last = comments.len() < COMMENTS_PER_PAGE;
+            result.extend(comments);
+            if last {
+                break;
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Replaces the body of the PR conversation comment with the given id.
+    pub fn edit_comment(&self, comment: u64, body: &str) -> impl Future<Item = Comment, Error = Error> {
+        let request = EditCommentRequest {
+            owner: self.owner.clone(),
+            repo: self.repo.clone(),
+            comment,
+            body: body.into(),
+        };
+
+        Client::execute(self.cfg.clone(), self.auth.clone(), request)
+            .and_then(|value| serde_json::from_value(value).map_err(Error::Json))
+    }
+
+    /// Dismisses the review of the PR, explaining why with the message.
+    pub fn dismiss_review(&self, number: u64, review: u64, message: &str) -> impl Future<Item = (), Error = Error> {
+        let request = DismissReviewRequest {
+            owner: self.owner.clone(),
+            repo: self.repo.clone(),
+            number,
+            review,
+            message: message.into(),
+        };
+
+        Client::execute(self.cfg.clone(), self.auth.clone(), request).map(|_| ())
+    }
+
+    /// Returns names of all files changed by the PR.
+    #[async]
+    pub fn files(self, number: u64) -> Result<Vec<String>, Error> {
+        let mut result = Vec::new();
+        for page in 1.. {
+            let request = ListFilesRequest {
+                owner: self.owner.clone(),
+                repo: self.repo.clone(),
+                number,
+                page,
+            };
+
+            let value = await!(Client::execute(self.cfg.clone(), self.auth.clone(), request))?;
+            let files: Vec<ChangedFile> = serde_json::from_value(value)?;
+            let last = files.len() < FILES_PER_PAGE;
+            result.extend(files.into_iter().map(|v| v.filename));
+            if last {
+                break;
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Requests reviews of the PR from the given users.
+    pub fn request_reviewers(&self, number: u64, reviewers: Vec<String>) -> impl Future<Item = (), Error = Error> {
+        let request = RequestReviewersRequest {
+            owner: self.owner.clone(),
+            repo: self.repo.clone(),
+            number,
+            reviewers,
+        };
+
+        Client::execute(self.cfg.clone(), self.auth.clone(), request).map(|_| ())
+    }
+
+    /// Adds the label to the PR.
+    pub fn add_label(&self, number: u64, label: &str) -> impl Future<Item = (), Error = Error> {
+        let request = AddLabelsRequest {
+            owner: self.owner.clone(),
+            repo: self.repo.clone(),
+            number,
+            labels: vec![label.into()],
+        };
+
+        Client::execute(self.cfg.clone(), self.auth.clone(), request).map(|_| ())
+    }
+
+    /// Removes the label from the PR.
+    pub fn remove_label(&self, number: u64, label: &str) -> impl Future<Item = (), Error = Error> {
+        let request = RemoveLabelRequest {
+            owner: self.owner.clone(),
+            repo: self.repo.clone(),
+            number,
+            label: label.into(),
+        };
+
+        Client::execute(self.cfg.clone(), self.auth.clone(), request).map(|_| ())
+    }
+
+    /// Reacts to the PR conversation comment with the given id.
+    pub fn react(&self, comment: u64, content: Reaction) -> impl Future<Item = (), Error = Error> {
+        let request = CreateReactionRequest {
+            owner: self.owner.clone(),
+            repo: self.repo.clone(),
+            comment,
+            content,
+        };
+
+        Client::execute(self.cfg.clone(), self.auth.clone(), request).map(|_| ())
+    }
+
+    /// Publishes the commit status of the given head.
+    pub fn set_status(&self, sha: &str, status: StatusInput) -> impl Future<Item = (), Error = Error> {
+        let request = CreateStatusRequest {
+            owner: self.owner.clone(),
+            repo: self.repo.clone(),
+            sha: sha.into(),
+            body: status,
+        };
+
+        Client::execute(self.cfg.clone(), self.auth.clone(), request).map(|_| ())
+    }
+
     pub fn state(&self, reference: &str) -> impl Future<Item = CombinedStatus, Error = Error> {
         let path = format!("repos/{}/{}/commits/{}/status", self.owner, self.repo, reference);
         let url = self.cfg.base_url.clone().with_path(&path);
@@ -122,14 +984,19 @@ impl PullRequestApi {
 
     #[async]
     pub fn execute_state(self, url: Url) -> Result<CombinedStatus, Error> {
+        let repository = format!("{}/{}", self.owner, self.repo);
+        let authorization = await!(self.auth.authorization(Some(repository)))?;
+
         let mut request = ClientRequest::get(url)
             .header("Accept", self.cfg.accept())
-            .header("Authorization", self.cfg.authorization())
+            .header("Authorization", authorization)
             .header("User-Agent", self.cfg.user_agent.clone())
             .finish()?;
 
         debug!("-> {} {}", request.method(), request.uri());
-        let response = await!(request.send())?;
+        let response = await!(request.send().then(Ok::<_, Error>))?;
+        record("commit_status", &response);
+        let response = response?;
         debug!("<- {}", response.status());
         let value: Value = await!(response.json())?;
         debug!("<- {}", value);
@@ -142,41 +1009,155 @@ impl PullRequestApi {
     }
 }
 
+#[derive(Clone)]
+pub struct RepositoryApi {
+    cfg: Config,
+    auth: Authenticator,
+    owner: String,
+    repo: String,
+}
+
+impl RepositoryApi {
+    /// Returns the permission level of the given user in the repository.
+    pub fn permission(&self, user: &str) -> impl Future<Item = Permission, Error = Error> {
+        let request = PermissionRequest {
+            owner: self.owner.clone(),
+            repo: self.repo.clone(),
+            user: user.into(),
+        };
+
+        Client::execute(self.cfg.clone(), self.auth.clone(), request).and_then(|value| {
+            serde_json::from_value(value["permission"].clone()).map_err(Error::Json)
+        })
+    }
+
+    /// Compares the head commit with the base one, both of which may be
+    /// either branch names or SHAs.
+    pub fn compare(&self, base: &str, head: &str) -> impl Future<Item = Comparison, Error = Error> {
+        let request = CompareRequest {
+            owner: self.owner.clone(),
+            repo: self.repo.clone(),
+            base: base.into(),
+            head: head.into(),
+        };
+
+        Client::execute(self.cfg.clone(), self.auth.clone(), request)
+            .and_then(|value| serde_json::from_value(value).map_err(Error::Json))
+    }
+
+    /// Returns the file at the given branch or SHA, or `None` if there is
+    /// no such file.
+    pub fn contents(&self, path: &str, reference: &str) -> impl Future<Item = Option<Contents>, Error = Error> {
+        let request = ContentsRequest {
+            owner: self.owner.clone(),
+            repo: self.repo.clone(),
+            path: path.into(),
+            reference: reference.into(),
+        };
+
+        Client::execute(self.cfg.clone(), self.auth.clone(), request).then(|result| match result {
+            Ok(value) => serde_json::from_value(value).map(Some).map_err(Error::Json),
+            Err(Error::Unexpected(StatusCode::NOT_FOUND)) => Ok(None),
+            Err(err) => Err(err),
+        })
+    }
+
+    /// Returns logins of members of the organization team, given by its
+    /// slug.
+    #[async]
+    pub fn team_members(self, org: String, team: String) -> Result<Vec<String>, Error> {
+        let mut result = Vec::new();
+        for page in 1.. {
+            let request = TeamMembersRequest {
+                owner: self.owner.clone(),
+                repo: self.repo.clone(),
+                org: org.clone(),
+                team: team.clone(),
+                page,
+            };
+
+            let value = await!(Client::execute(self.cfg.clone(), self.auth.clone(), request))?;
+            let members: Vec<Actor> = serde_json::from_value(value)?;
+            let last = members.len() < MEMBERS_PER_PAGE;
+            result.extend(members.into_iter().map(|v| v.login));
+            if last {
+                break;
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+#[derive(Clone)]
 pub struct Client {
     cfg: Config,
+    auth: Authenticator,
 }
 
 impl Client {
-    pub fn new(cfg: Config) -> Self {
-        Self { cfg }
+    /// Constructs a new client.
+    ///
+    /// Fails if app credentials are configured, but the private key can not
+    /// be loaded.
+    pub fn new(cfg: Config) -> Result<Self, Error> {
+        let auth = Authenticator::new(cfg.base_url.clone(), cfg.user_agent.clone(), &cfg.credentials)?;
+
+        Ok(Self { cfg, auth })
     }
 
-//    pub fn repository(&self, owner: &str, repo: &str) -> RepositoryApi {
-//        unimplemented!()
-//    }
+    pub fn authenticator(&self) -> &Authenticator {
+        &self.auth
+    }
+
+    pub fn repository(&self, owner: &str, repo: &str) -> RepositoryApi {
+        RepositoryApi {
+            cfg: self.cfg.clone(),
+            auth: self.auth.clone(),
+            owner: owner.into(),
+            repo: repo.into(),
+        }
+    }
 
     pub fn pull_request(&self, owner: &str, repo: &str) -> PullRequestApi {
         PullRequestApi {
             cfg: self.cfg.clone(),
+            auth: self.auth.clone(),
             owner: owner.into(),
             repo: repo.into(),
         }
     }
 
     pub fn merge(&self, request: MergeRequest) -> impl Future<Item = Value, Error = Error> {
-        Client::execute(self.cfg.clone(), request)
+        Client::execute(self.cfg.clone(), self.auth.clone(), request)
+    }
+
+    /// Returns the current rate limit status of the core API.
+    ///
+    /// Requesting the rate limit status does not count against the limit,
+    /// which makes it suitable for probing GitHub reachability.
+    pub fn rate_limit(&self) -> impl Future<Item = RateLimit, Error = Error> {
+        Client::execute(self.cfg.clone(), self.auth.clone(), RateLimitRequest).and_then(|value| {
+            serde_json::from_value(value["resources"]["core"].clone()).map_err(Error::Json)
+        })
+    }
+
+    /// Returns GitHub service information, including webhook source ranges.
+    pub fn meta(&self) -> impl Future<Item = Meta, Error = Error> {
+        Client::execute(self.cfg.clone(), self.auth.clone(), MetaRequest)
+            .and_then(|value| serde_json::from_value(value).map_err(Error::Json))
     }
 
     #[async]
-    fn execute<R: Request + 'static>(cfg: Config, request: R) -> Result<Value, Error> {
+    fn execute<R: Request + 'static>(cfg: Config, auth: Authenticator, request: R) -> Result<Value, Error> {
         let uri = format!("{}/{}", cfg.base_url, request.path());
-        let authorization = format!("token {}", cfg.oauth_token);
+        let authorization = await!(auth.authorization(request.repository()))?;
 
         let mut req = ClientRequest::build()
             .method(R::method())
             .uri(uri)
-            .header("Accept", "application/vnd.github.v3+json")
-            .header("Authorization", authorization.clone())
+            .header("Accept", R::accept())
+            .header("Authorization", authorization)
             .header("User-Agent", cfg.user_agent.clone())
             .finish()?;
 
@@ -184,16 +1165,19 @@ impl Client {
             req.set_body(body?);
         }
 
-        debug!("-> {:?}", req);
-        let resp = await!(req.send())?;
+        debug!("-> {} {} {:?}", req.method(), req.uri(), RedactedHeaders(req.headers()));
+        let resp = await!(req.send().then(Ok::<_, Error>))?;
+        record(R::endpoint(), &resp);
+        let resp = resp?;
         debug!("<- {:?}", resp);
         match resp.status() {
-            StatusCode::CREATED => {
+            StatusCode::OK | StatusCode::CREATED => {
                 let body = await!(resp.json())?;
                 debug!("<- {:?}", body);
                 Ok(body)
             }
-            _status => Ok(Value::Null),
+            StatusCode::NO_CONTENT => Ok(Value::Null),
+            status => Err(Error::Unexpected(status)),
         }
     }
 }
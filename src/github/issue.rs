@@ -0,0 +1,17 @@
+/// Reaction to an issue or PR comment.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum Reaction {
+    #[serde(rename = "+1")]
+    ThumbsUp,
+    #[serde(rename = "-1")]
+    ThumbsDown,
+    #[serde(rename = "confused")]
+    Confused,
+}
+
+/// Issue or PR comment.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Comment {
+    pub id: u64,
+    pub body: String,
+}
@@ -0,0 +1,6 @@
+/// GitHub service information, of which we only need webhook source ranges.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Meta {
+    /// CIDR ranges GitHub delivers webhooks from.
+    pub hooks: Vec<String>,
+}
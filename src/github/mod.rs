@@ -2,13 +2,25 @@ use std::borrow::Cow;
 use std::fmt::{self, Display, Formatter};
 
 use actix_web::http::header::{HeaderValue, IntoHeaderValue, InvalidHeaderValue};
-use serde::{de, Deserialize, Deserializer};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use url::Url;
 
+pub use self::auth::*;
 pub use self::client::*;
+pub use self::issue::*;
+pub use self::meta::*;
+pub use self::pull_request::*;
+pub use self::rate_limit::*;
+pub use self::repository::*;
 pub use self::status::*;
 
+mod auth;
 mod client;
+mod issue;
+mod meta;
+mod pull_request;
+mod rate_limit;
+mod repository;
 mod status;
 
 /// Represents an entry point to the github API.
@@ -18,6 +30,12 @@ mod status;
 pub struct BaseUrl(Url);
 
 impl BaseUrl {
+    pub fn as_url(&self) -> &Url {
+        match self {
+            BaseUrl(v) => v,
+        }
+    }
+
     pub fn with_path(self, path: &str) -> Url {
         match self {
             BaseUrl(mut v) => {
@@ -53,6 +71,12 @@ impl<'de> Deserialize<'de> for BaseUrl {
     }
 }
 
+impl Serialize for BaseUrl {
+    fn serialize<S: Serializer>(&self, se: S) -> Result<S::Ok, S::Error> {
+        se.serialize_str(self.as_url().as_str())
+    }
+}
+
 impl IntoHeaderValue for BaseUrl {
     type Error = InvalidHeaderValue;
 
@@ -0,0 +1,340 @@
+use serde::{Deserialize, Deserializer};
+
+/// GraphQL connection, of which we only fetch nodes and, for some of them,
+/// whether there are more.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Nodes<T> {
+    nodes: Vec<T>,
+    #[serde(default)]
+    page_info: PageInfo,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageInfo {
+    pub has_next_page: bool,
+    pub end_cursor: Option<String>,
+}
+
+/// Single page of open PRs.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PullRequestPage {
+    pub nodes: Vec<PullRequest>,
+    pub page_info: PageInfo,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct Label {
+    name: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct PullRequestCommit {
+    commit: Commit,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Commit {
+    oid: String,
+    status_check_rollup: Option<StatusCheckRollup>,
+}
+
+/// Both commit statuses and check runs, like GitHub Actions jobs.
+#[derive(Clone, Debug, Deserialize)]
+struct StatusCheckRollup {
+    contexts: Nodes<RollupContext>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "__typename")]
+enum RollupContext {
+    CheckRun {
+        name: String,
+        /// Either `COMPLETED` or one of the states before, like `QUEUED`.
+        status: String,
+        /// Set once completed, like `SUCCESS` or `TIMED_OUT`.
+        conclusion: Option<String>,
+    },
+    StatusContext {
+        context: String,
+        state: StatusState,
+    },
+}
+
+impl RollupContext {
+    fn to_status(&self) -> StatusContext {
+        match self {
+            RollupContext::CheckRun { name, status, conclusion } => {
+                let state = match (status.as_str(), conclusion.as_ref().map(|v| v.as_str())) {
+                    ("COMPLETED", Some("SUCCESS")) |
+                    ("COMPLETED", Some("NEUTRAL")) |
+                    ("COMPLETED", Some("SKIPPED")) => StatusState::Success,
+                    ("COMPLETED", ..) => StatusState::Failure,
+                    _ => StatusState::Pending,
+                };
+
+                StatusContext {
+                    context: name.clone(),
+                    state,
+                }
+            }
+            RollupContext::StatusContext { context, state } => StatusContext {
+                context: context.clone(),
+                state: *state,
+            },
+        }
+    }
+}
+
+/// Mergeability of a PR, either as GraphQL `mergeable` or as REST
+/// `mergeable_state`, which is more detailed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MergeableState {
+    /// `MERGEABLE`, `clean` or `has_hooks`.
+    Mergeable,
+    /// `CONFLICTING`.
+    Conflicting,
+    /// GitHub has not computed mergeability yet, which it does lazily in the
+    /// background.
+    Unknown,
+    /// Mergeable, but the head branch is behind the base.
+    Behind,
+    /// Mergeable, but blocked by branch protection.
+    Blocked,
+    /// The merge commit can not be created cleanly, i.e. REST counterpart of
+    /// `CONFLICTING`.
+    Dirty,
+    /// Mergeable, but some status checks have not passed.
+    Unstable,
+    /// The PR is a draft, which can not be merged until marked as ready for
+    /// review.
+    Draft,
+}
+
+impl MergeableState {
+    /// Parses either GraphQL or REST state, case-insensitively.
+    pub fn parse(value: &str) -> Option<Self> {
+        let state = match value.to_lowercase().as_str() {
+            "mergeable" | "clean" | "has_hooks" => MergeableState::Mergeable,
+            "conflicting" => MergeableState::Conflicting,
+            "unknown" => MergeableState::Unknown,
+            "behind" => MergeableState::Behind,
+            "blocked" => MergeableState::Blocked,
+            "dirty" => MergeableState::Dirty,
+            "unstable" => MergeableState::Unstable,
+            "draft" => MergeableState::Draft,
+            _ => return None,
+        };
+
+        Some(state)
+    }
+
+    /// Interprets REST `mergeable` and `mergeable_state` fields of a PR.
+    ///
+    /// States this enum does not know are settled, so they are mapped
+    /// according to `mergeable` instead of being polled for forever.
+    pub fn from_rest(mergeable: Option<bool>, state: Option<&str>) -> Self {
+        match (mergeable, state.and_then(MergeableState::parse)) {
+            (None, ..) => MergeableState::Unknown,
+            (Some(..), Some(state)) => state,
+            (Some(false), None) => MergeableState::Dirty,
+            (Some(true), None) => MergeableState::Mergeable,
+        }
+    }
+
+    /// Returns `true` if the PR can not be merged without resolving conflicts.
+    pub fn is_conflicting(&self) -> bool {
+        match self {
+            MergeableState::Conflicting | MergeableState::Dirty => true,
+            _ => false,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for MergeableState {
+    /// States unknown to the bot are treated as not yet computed.
+    fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        let value: String = Deserialize::deserialize(de)?;
+        Ok(MergeableState::parse(&value).unwrap_or(MergeableState::Unknown))
+    }
+}
+
+/// State of a single status context.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum StatusState {
+    Error,
+    Expected,
+    Failure,
+    Pending,
+    Success,
+}
+
+/// Individual status check of the head commit.
+#[derive(Clone, Debug, Deserialize)]
+pub struct StatusContext {
+    pub context: String,
+    pub state: StatusState,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ReviewState {
+    Approved,
+    ChangesRequested,
+    Commented,
+    Dismissed,
+    Pending,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Actor {
+    pub login: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CommitRef {
+    pub oid: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Review {
+    /// REST id of the review, which is required to dismiss it.
+    pub database_id: Option<u64>,
+    /// Author of the review, `None` if the account was deleted.
+    pub author: Option<Actor>,
+    /// Author association with the repository, like `MEMBER` or `OWNER`.
+    pub author_association: String,
+    pub state: ReviewState,
+    /// Commit the review was submitted for.
+    pub commit: Option<CommitRef>,
+}
+
+/// Requested reviewer, whose login is `None` for teams.
+#[derive(Clone, Debug, Deserialize)]
+struct RequestedReviewer {
+    login: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReviewRequest {
+    requested_reviewer: Option<RequestedReviewer>,
+}
+
+/// File changed by a PR, as listed by the REST API.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ChangedFile {
+    pub filename: String,
+}
+
+/// Open pull request, as fetched by the `pull_requests.graphql` query.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PullRequest {
+    pub number: u64,
+    pub title: String,
+    /// Link to the PR page.
+    pub url: String,
+    pub mergeable: MergeableState,
+    pub base_ref_name: String,
+    pub head_ref_name: String,
+    pub head_ref_oid: String,
+    /// Whether the PR comes from a fork.
+    pub is_cross_repository: bool,
+    pub is_draft: bool,
+    labels: Nodes<Label>,
+    commits: Nodes<PullRequestCommit>,
+    review_requests: Nodes<ReviewRequest>,
+    reviews: Nodes<Review>,
+}
+
+impl PullRequest {
+    pub fn labels<'a>(&'a self) -> impl Iterator<Item = &'a str> {
+        self.labels.nodes.iter().map(|label| label.name.as_str())
+    }
+
+    /// Returns status checks of the head commit, where check runs are
+    /// named by their names and completed ones either succeed or fail.
+    pub fn statuses(&self) -> Vec<StatusContext> {
+        self.rollup()
+            .map(|v| v.contexts.nodes.iter().map(|v| v.to_status()).collect())
+            .unwrap_or_default()
+    }
+
+    fn rollup(&self) -> Option<&StatusCheckRollup> {
+        self.commits
+            .nodes
+            .iter()
+            .find(|v| v.commit.oid == self.head_ref_oid)
+            .and_then(|v| v.commit.status_check_rollup.as_ref())
+    }
+
+    /// Returns names of fetched connections that have more nodes than
+    /// fetched, which makes the PR impossible to evaluate reliably.
+    pub fn truncated(&self) -> Vec<&'static str> {
+        let mut result = Vec::new();
+        if self.labels.page_info.has_next_page {
+            result.push("labels");
+        }
+        if self.rollup().map_or(false, |v| v.contexts.page_info.has_next_page) {
+            result.push("checks");
+        }
+        if self.review_requests.page_info.has_next_page {
+            result.push("review requests");
+        }
+        if self.reviews.page_info.has_next_page {
+            result.push("reviews");
+        }
+        result
+    }
+
+    /// Returns logins of users requested to review the PR, who have not
+    /// reviewed it yet.
+    pub fn requested_reviewers<'a>(&'a self) -> impl Iterator<Item = &'a str> {
+        self.review_requests
+            .nodes
+            .iter()
+            .filter_map(|v| v.requested_reviewer.as_ref())
+            .filter_map(|v| v.login.as_ref())
+            .map(|v| v.as_str())
+    }
+
+    /// Returns reviews in chronological order.
+    pub fn reviews(&self) -> &[Review] {
+        &self.reviews.nodes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MergeableState;
+
+    #[test]
+    fn interprets_rest_mergeability() {
+        let cases = [
+            (None, None, MergeableState::Unknown),
+            (None, Some("clean"), MergeableState::Unknown),
+            (Some(true), Some("unknown"), MergeableState::Unknown),
+            (Some(true), Some("clean"), MergeableState::Mergeable),
+            (Some(true), Some("has_hooks"), MergeableState::Mergeable),
+            (Some(true), Some("behind"), MergeableState::Behind),
+            (Some(true), Some("blocked"), MergeableState::Blocked),
+            (Some(true), Some("unstable"), MergeableState::Unstable),
+            (Some(false), Some("dirty"), MergeableState::Dirty),
+            (Some(true), Some("draft"), MergeableState::Draft),
+            (Some(false), Some("draft"), MergeableState::Draft),
+            (Some(true), Some("unexpected"), MergeableState::Mergeable),
+            (Some(true), None, MergeableState::Mergeable),
+            (Some(false), Some("unexpected"), MergeableState::Dirty),
+        ];
+
+        for &(mergeable, state, expected) in &cases {
+            assert_eq!(expected, MergeableState::from_rest(mergeable, state), "{:?} {:?}", mergeable, state);
+        }
+    }
+}
@@ -0,0 +1,10 @@
+/// Rate limit status of the core GitHub API for the authenticated user.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RateLimit {
+    /// Maximum number of requests per hour.
+    pub limit: u32,
+    /// Number of requests remaining in the current window.
+    pub remaining: u32,
+    /// Time when the current window resets, in UTC epoch seconds.
+    pub reset: u64,
+}
@@ -0,0 +1,61 @@
+use base64;
+
+/// Permission level of a user in a repository.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    Admin,
+    Write,
+    Read,
+    None,
+}
+
+impl Permission {
+    /// Returns `true` if the user may push to the repository.
+    pub fn can_push(&self) -> bool {
+        match self {
+            Permission::Admin | Permission::Write => true,
+            Permission::Read | Permission::None => false,
+        }
+    }
+}
+
+/// Comparison of two commits, of which we only need their relation.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Comparison {
+    /// Either `ahead`, `behind`, `diverged` or `identical`.
+    pub status: String,
+    /// Number of head commits missing in the base.
+    pub ahead_by: u64,
+    /// Number of base commits missing in the head.
+    pub behind_by: u64,
+}
+
+impl Comparison {
+    /// Returns `true` if the head lacks commits of the base.
+    pub fn is_behind(&self) -> bool {
+        self.behind_by > 0
+    }
+}
+
+/// File fetched via the contents API.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Contents {
+    /// File contents, encoded as specified by `encoding`.
+    pub content: String,
+    pub encoding: String,
+}
+
+impl Contents {
+    /// Decodes the file contents, returning `None` if they are not valid
+    /// base64-encoded UTF-8.
+    pub fn decode(&self) -> Option<String> {
+        if self.encoding != "base64" {
+            return None;
+        }
+
+        // GitHub wraps the encoded contents into lines.
+        let content: String = self.content.split_whitespace().collect();
+        base64::decode(&content).ok().and_then(|v| String::from_utf8(v).ok())
+    }
+}
@@ -24,3 +24,25 @@ impl<'a> TryFrom<&'a str> for CombinedStatus {
         }
     }
 }
+
+/// State of a commit status to publish.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommitState {
+    Pending,
+    Success,
+    Failure,
+    Error,
+}
+
+/// Commit status to publish for a single context.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StatusInput {
+    pub state: CommitState,
+    /// Link to the details page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_url: Option<String>,
+    /// Short description, which GitHub truncates to 140 characters.
+    pub description: String,
+    pub context: String,
+}
@@ -0,0 +1,176 @@
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use actix_web::{HttpRequest, HttpResponse};
+use futures::prelude::*;
+use serde_json::Value;
+use tokio::timer::Interval;
+
+use github::{Client, RateLimit};
+use metrics;
+use runtime::Status;
+
+/// How often GitHub is probed for reachability and rate limit budget.
+const PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Maximum age of the last successful probe to consider GitHub reachable.
+const PROBE_TTL: Duration = Duration::from_secs(90);
+
+/// Minimum number of remaining API requests to consider the bot ready.
+const MIN_RATE_LIMIT_REMAINING: u32 = 100;
+
+/// Build information reported by the `/version` endpoint.
+#[derive(Clone, Debug, Serialize)]
+pub struct Version {
+    version: &'static str,
+    sha: &'static str,
+    config: String,
+}
+
+impl Version {
+    pub fn new(config: &str) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            sha: env!("SONMHUB_GIT_SHA"),
+            config: config.into(),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Probe {
+    Pending,
+    Ok(Instant, RateLimit),
+    Failed(Instant, String),
+}
+
+/// Health state of the application, shared between HTTP workers.
+#[derive(Clone, Debug)]
+pub struct Health {
+    status: Status,
+    capacity: usize,
+    probe: Arc<Mutex<Probe>>,
+    version: Arc<RwLock<Version>>,
+}
+
+impl Health {
+    /// Constructs a new health state.
+    ///
+    /// The `capacity` is the capacity of the runtime queue, which is used to
+    /// detect whether the runtime is still consuming events.
+    pub fn new(status: Status, capacity: usize, version: Version) -> Self {
+        Self {
+            status,
+            capacity,
+            probe: Arc::new(Mutex::new(Probe::Pending)),
+            version: Arc::new(RwLock::new(version)),
+        }
+    }
+
+    /// Updates the config hash reported by `/version` after a reload.
+    pub fn set_config(&self, config: &str) {
+        self.version.write().expect("version lock is poisoned").config = config.into();
+    }
+
+    /// Periodically probes GitHub, remembering its reachability and the rate
+    /// limit budget.
+    #[async]
+    pub fn watch(self, client: Client) -> Result<(), ()> {
+        let ticks = Interval::new(Instant::now(), PROBE_INTERVAL)
+            .map_err(|err| error!("health probe timer failed: {}", err));
+
+        #[async]
+        for _ in ticks {
+            let probe = match await!(client.rate_limit().then(Ok::<_, ()>))? {
+                Ok(rate_limit) => {
+                    debug!("GitHub rate limit: {}/{}", rate_limit.remaining, rate_limit.limit);
+                    metrics::GITHUB_RATE_LIMIT_REMAINING.set(rate_limit.remaining as i64);
+                    Probe::Ok(Instant::now(), rate_limit)
+                }
+                Err(err) => {
+                    warn!("failed to probe GitHub: {:?}", err);
+                    Probe::Failed(Instant::now(), format!("{:?}", err))
+                }
+            };
+
+            *self.probe.lock().expect("health lock is poisoned") = probe;
+        }
+
+        Ok(())
+    }
+
+    /// Checks whether the application is ready to process hooks, returning
+    /// the verdict with the details of each check.
+    fn readiness(&self) -> (bool, Value) {
+        let depth = self.status.depth();
+        let runtime = self.status.is_healthy() && depth < self.capacity;
+
+        let (github, rate_limit, details) = match *self.probe.lock().expect("health lock is poisoned") {
+            Probe::Pending => (false, false, json!({"error": "not probed yet"})),
+            Probe::Ok(timestamp, rate_limit) => {
+                let fresh = timestamp.elapsed() < PROBE_TTL;
+                let budget = rate_limit.remaining >= MIN_RATE_LIMIT_REMAINING;
+                (fresh, budget, json!({"rate_limit": rate_limit}))
+            }
+            Probe::Failed(.., ref err) => (false, false, json!({"error": err})),
+        };
+
+        let ready = runtime && github && rate_limit;
+        let checks = json!({
+            "runtime": {"ok": runtime, "depth": depth},
+            "github": {"ok": github, "details": details},
+            "rate_limit": {"ok": rate_limit},
+        });
+
+        (ready, checks)
+    }
+}
+
+/// Reports that the process is alive.
+pub fn healthz<S>(_request: HttpRequest<S>) -> HttpResponse {
+    HttpResponse::Ok().json(json!({"status": "ok"}))
+}
+
+/// Reports whether the runtime is consuming events, GitHub is reachable and
+/// there is enough rate limit budget.
+pub fn readyz<S: AsRef<Health>>(request: HttpRequest<S>) -> HttpResponse {
+    let (ready, checks) = request.state().as_ref().readiness();
+
+    let mut response = if ready {
+        HttpResponse::Ok()
+    } else {
+        HttpResponse::ServiceUnavailable()
+    };
+
+    response.json(json!({"ready": ready, "checks": checks}))
+}
+
+/// Reports the crate version, git SHA it was built from and the config hash.
+pub fn version<S: AsRef<Health>>(request: HttpRequest<S>) -> HttpResponse {
+    let version = request.state().as_ref().version.read().expect("version lock is poisoned").clone();
+    HttpResponse::Ok().json(version)
+}
+
+#[cfg(test)]
+mod test {
+    use runtime::Status;
+
+    use super::{Health, Version};
+
+    #[test]
+    fn readiness_fails_when_runtime_stalls() {
+        let status = Status::new();
+        let health = Health::new(status.clone(), 2, Version::new("hash"));
+
+        status.enqueued();
+        let (_, checks) = health.readiness();
+        assert_eq!(true, checks["runtime"]["ok"]);
+
+        // The runtime consumes nothing, so the queue fills up.
+        status.enqueued();
+        let (ready, checks) = health.readiness();
+        assert!(!ready);
+        assert_eq!(false, checks["runtime"]["ok"]);
+        assert_eq!(2, checks["runtime"]["depth"]);
+    }
+}
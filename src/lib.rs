@@ -2,38 +2,71 @@
 //!
 //! A PR can be merged if all of the following conditions are met:
 //! - It has neither `wip` label nor `wip` word in the title.
+//! - It is not a draft.
 //! - All status checks have passed.
 //! - There is no non `APPROVED` reviews from `MEMBER` or `OWNER` users with
 //! push access to this   repository. At least N approves is required.
 //!   https://api.github.com/repos/<owner>/<repo>/pulls/<num>/reviews
 //!   https://api.github.com/repos/<owner>/<repo>/collaborators
+//! - Optionally, an owner of each `CODEOWNERS` pattern matching changed
+//!   files has approved, where teams count by their members.
 //!
-//! All these checks are being executed every 60 sec.
+//! All these checks are being executed for each configured repository on
+//! relevant webhooks and every `merge.interval` seconds. The number of
+//! approvals and the `wip` marker are configured per repository.
+//!
+//! Users with push access control the merge queue with PR comments, like
+//! `@sonmbot r+`, `r=user`, `retry`, `cancel`, `priority=N` or `rollup`.
+//! Approved PRs are merged first, in the queue order.
+//!
+//! Reviewers of new PRs may be requested automatically, either from
+//! `CODEOWNERS` or from a configured team.
+//!
+//! Every decision is appended to `audit.log`, which is queried with the
+//! `audit` subcommand.
 
 #![feature(proc_macro, proc_macro_non_items, generators, try_from)]
 
 extern crate actix;
 extern crate actix_web;
 extern crate ansi_term;
+extern crate atty;
+extern crate base64;
 extern crate chrono;
 extern crate crypto;
 extern crate fern;
 extern crate futures_await as futures;
+extern crate jsonwebtoken;
+#[macro_use]
+extern crate lazy_static;
 #[macro_use]
 extern crate log;
+extern crate openssl;
+#[macro_use]
+extern crate prometheus;
 extern crate rustc_hex;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_ignored;
 #[macro_use]
 extern crate serde_json;
 extern crate serde_yaml;
 extern crate tokio;
+extern crate tokio_uds;
 extern crate url;
 
+pub mod allowlist;
+pub mod audit;
 pub mod bot;
 pub mod config;
+pub mod deliveries;
 pub mod github;
+pub mod health;
 pub mod logging;
+pub mod metrics;
+pub mod reload;
+pub mod runtime;
 pub mod secure;
 pub mod server;
+pub mod tls;
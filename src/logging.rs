@@ -1,35 +1,350 @@
-use std::{self, error::Error};
+use std::{self, cell::RefCell, error::Error, fmt, os::unix::net::UnixDatagram, path::Path};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::sync::RwLock;
 
 use ansi_term::Colour;
+use atty;
+use futures::{Future, Poll};
 use log::Level;
 
 use chrono;
-use fern::Dispatch;
-use log;
-
-pub fn init() -> Result<(), Box<Error>> {
-    Dispatch::new()
-        .format(|out, message, record| {
-            let timestamp = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S%.6f").to_string();
-
-            let level = match record.level() {
-                Level::Error => Colour::Red,
-                Level::Warn => Colour::Yellow,
-                Level::Info => Colour::Blue,
-                Level::Debug |
-                Level::Trace => Colour::White,
-            };
-
-            out.finish(format_args!(
-                "{} {} {}",
-                timestamp,
-                level.paint(format!("{:<5}", record.level())),
-                message
-            ))
-        })
-        .level(log::LevelFilter::Off)
-        .level_for("sonmhub", log::LevelFilter::Debug)
-        .chain(std::io::stdout())
-        .apply()?;
+use fern::{self, Dispatch, FormatCallback, Output};
+use log::{self, LevelFilter, Metadata, Record};
+
+use config::{LogFormat, LogTarget, LoggingConfig};
+
+static REQUEST_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+
+const REDACTED: &str = "<redacted>";
+
+lazy_static! {
+    static ref SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+    static ref LEVELS: RwLock<Levels> = RwLock::new(Levels {
+        level: LevelFilter::Off,
+        modules: BTreeMap::new(),
+    });
+}
+
+/// Log levels, which may be changed after the logger is installed.
+#[derive(Debug)]
+struct Levels {
+    level: LevelFilter,
+    modules: BTreeMap<String, LevelFilter>,
+}
+
+impl Levels {
+    fn new(cfg: &LoggingConfig) -> Self {
+        Self {
+            level: cfg.level(),
+            modules: cfg.modules().clone(),
+        }
+    }
+
+    /// Returns the level for the given target, which is the level of the
+    /// most specific module it belongs to.
+    fn level_for(&self, target: &str) -> LevelFilter {
+        let belongs = |module: &str| {
+            target == module || (target.starts_with(module) && target[module.len()..].starts_with("::"))
+        };
+
+        let module = self.modules.iter().filter(|(module, ..)| belongs(module)).max_by_key(|(module, ..)| module.len());
+
+        match module {
+            Some((.., level)) => *level,
+            None if belongs("sonmhub") => self.level,
+            None => LevelFilter::Off,
+        }
+    }
+
+    fn max(&self) -> LevelFilter {
+        self.modules.values().cloned().fold(self.level, std::cmp::max)
+    }
+
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level_for(metadata.target())
+    }
+}
+
+fn set_levels(cfg: &LoggingConfig) {
+    let levels = Levels::new(cfg);
+    log::set_max_level(levels.max());
+    *LEVELS.write().expect("levels lock is poisoned") = levels;
+}
+
+/// Registers a secret that must never reach the log sink.
+///
+/// Every log line is checked for registered secrets before being written,
+/// replacing each occurrence with a placeholder. This is the last line of
+/// defense for secrets that leak through `Debug` of foreign types.
+pub fn register_secret(secret: &str) {
+    if secret.is_empty() {
+        return;
+    }
+
+    let mut secrets = SECRETS.write().expect("secrets lock is poisoned");
+    if !secrets.iter().any(|v| v == secret) {
+        secrets.push(secret.into());
+    }
+}
+
+fn redact(message: &fmt::Arguments) -> String {
+    let mut message = message.to_string();
+    for secret in SECRETS.read().expect("secrets lock is poisoned").iter() {
+        if message.contains(secret.as_str()) {
+            message = message.replace(secret.as_str(), REDACTED);
+        }
+    }
+
+    message
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Context> = RefCell::new(Context::default());
+}
+
+/// Identifiers that are attached to every log line emitted within a scope.
+#[derive(Clone, Debug, Default)]
+pub struct Context {
+    request: Option<String>,
+    delivery: Option<String>,
+}
+
+impl Context {
+    /// Constructs a new context with a freshly generated request id.
+    pub fn request() -> Self {
+        let id = REQUEST_ID.fetch_add(1, Ordering::SeqCst);
+
+        Self {
+            request: Some(format!("{:08x}", id)),
+            delivery: None,
+        }
+    }
+
+    pub fn with_delivery(mut self, delivery: Option<String>) -> Self {
+        self.delivery = delivery;
+        self
+    }
+
+    fn current() -> Self {
+        CONTEXT.with(|context| context.borrow().clone())
+    }
+}
+
+/// Calls the given function with the context installed for the current
+/// thread.
+pub fn with_context<F, R>(context: &Context, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let prev = CONTEXT.with(|v| std::mem::replace(&mut *v.borrow_mut(), context.clone()));
+    let result = f();
+    CONTEXT.with(|v| *v.borrow_mut() = prev);
+
+    result
+}
+
+/// A future that installs its logging context each time it is polled.
+///
+/// Futures from different requests are interleaved on the same thread, so the
+/// context can not be simply set once before the future is spawned.
+pub struct Scoped<F> {
+    context: Context,
+    future: F,
+}
+
+impl<F: Future> Future for Scoped<F> {
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let future = &mut self.future;
+        with_context(&self.context, || future.poll())
+    }
+}
+
+/// Wraps the given future, attaching the context to every log line emitted
+/// while it is polled.
+pub fn scoped<F: Future>(context: Context, future: F) -> Scoped<F> {
+    Scoped { context, future }
+}
+
+fn timestamp() -> String {
+    chrono::Utc::now().format("%Y-%m-%d %H:%M:%S%.6f").to_string()
+}
+
+fn format_text(out: FormatCallback, message: &fmt::Arguments, record: &Record, color: bool) {
+    let context = Context::current();
+
+    let mut ids = String::new();
+    if let Some(request) = context.request {
+        ids.push_str(&format!(" [request={}]", request));
+    }
+    if let Some(delivery) = context.delivery {
+        ids.push_str(&format!(" [delivery={}]", delivery));
+    }
+
+    let level = format!("{:<5}", record.level());
+    let level = if color {
+        let colour = match record.level() {
+            Level::Error => Colour::Red,
+            Level::Warn => Colour::Yellow,
+            Level::Info => Colour::Blue,
+            Level::Debug |
+            Level::Trace => Colour::White,
+        };
+
+        colour.paint(level).to_string()
+    } else {
+        level
+    };
+
+    out.finish(format_args!("{} {}{} {}", timestamp(), level, ids, redact(message)))
+}
+
+fn format_json(out: FormatCallback, message: &fmt::Arguments, record: &Record) {
+    let context = Context::current();
+
+    let line = json!({
+        "timestamp": timestamp(),
+        "level": record.level().to_string(),
+        "target": record.target(),
+        "message": redact(message),
+        "request": context.request,
+        "delivery": context.delivery,
+    });
+
+    out.finish(format_args!("{}", line))
+}
+
+/// Creates an output that sends each log line as a datagram into the syslog
+/// socket.
+fn syslog(path: &Path) -> Result<Output, Box<Error>> {
+    let socket = UnixDatagram::unbound()?;
+    socket.connect(path)?;
+
+    let output = Output::call(move |record| {
+        // Facility is `daemon`.
+        let severity = match record.level() {
+            Level::Error => 3,
+            Level::Warn => 4,
+            Level::Info => 6,
+            Level::Debug | Level::Trace => 7,
+        };
+        let line = format!("<{}>sonmhub: {}", 3 * 8 + severity, record.args());
+
+        // There is nowhere to report this error.
+        let _ = socket.send(line.as_bytes());
+    });
+
+    Ok(output)
+}
+
+/// Builds a dispatch with formatting and filtering, but without an output.
+///
+/// Levels are taken from the given config, but are checked on each record,
+/// so that they can be changed later via `reload`.
+fn dispatch(cfg: &LoggingConfig, color: bool) -> Dispatch {
+    set_levels(cfg);
+
+    let dispatch = match cfg.format() {
+        LogFormat::Text => {
+            Dispatch::new().format(move |out, message, record| format_text(out, message, record, color))
+        }
+        LogFormat::Json => Dispatch::new().format(format_json),
+    };
+
+    dispatch.filter(|metadata| LEVELS.read().expect("levels lock is poisoned").enabled(metadata))
+}
+
+pub fn init(cfg: &LoggingConfig) -> Result<(), Box<Error>> {
+    let color = match cfg.target() {
+        LogTarget::Stdout => atty::is(atty::Stream::Stdout),
+        LogTarget::File(..) | LogTarget::Syslog(..) => false,
+    };
+
+    let output: Output = match cfg.target() {
+        LogTarget::Stdout => std::io::stdout().into(),
+        LogTarget::File(path) => fern::log_file(path)?.into(),
+        LogTarget::Syslog(path) => syslog(path)?,
+    };
+
+    dispatch(cfg, color).chain(output).apply()?;
+
+    // Installing the logger resets the max level.
+    set_levels(cfg);
     Ok(())
 }
+
+/// Applies log levels of the reloaded config.
+///
+/// The format and the target can not be changed without restart.
+pub fn reload(cfg: &LoggingConfig) {
+    set_levels(cfg);
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use fern::Output;
+    use log::{Level, Record};
+    use serde_yaml;
+
+    use config::{GithubConfig, LoggingConfig};
+
+    use super::{dispatch, register_secret};
+
+    const TOKEN: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    fn capture(cfg: &LoggingConfig) -> Vec<String> {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+
+        let (_, log) = {
+            let lines = lines.clone();
+            dispatch(cfg, false)
+                .chain(Output::call(move |record| {
+                    lines.lock().unwrap().push(record.args().to_string());
+                }))
+                .into_log()
+        };
+
+        let github: GithubConfig = serde_yaml::from_str(&format!("oauth_token: {}", TOKEN)).unwrap();
+
+        log.log(&Record::builder()
+            .args(format_args!("-> Authorization: token {}", TOKEN))
+            .level(Level::Debug)
+            .target("sonmhub::github")
+            .build());
+        log.log(&Record::builder()
+            .args(format_args!("config: {:?}", github))
+            .level(Level::Info)
+            .target("sonmhub::bot")
+            .build());
+
+        let lines = lines.lock().unwrap().clone();
+        lines
+    }
+
+    #[test]
+    fn token_never_reaches_sink() {
+        register_secret(TOKEN);
+
+        for cfg in &["format: text", "format: json"] {
+            let cfg: LoggingConfig = serde_yaml::from_str(cfg).unwrap();
+            let lines = capture(&cfg);
+
+            assert_eq!(2, lines.len());
+            for line in lines {
+                assert!(!line.contains(TOKEN), "token leaked: {}", line);
+            }
+        }
+    }
+
+    #[test]
+    fn github_config_debug_is_redacted() {
+        let github: GithubConfig = serde_yaml::from_str(&format!("oauth_token: {}", TOKEN)).unwrap();
+
+        assert!(!format!("{:?}", github).contains(TOKEN));
+    }
+}
@@ -1,16 +1,86 @@
 #[macro_use]
 extern crate clap;
+extern crate chrono;
+extern crate serde_json;
+extern crate serde_yaml;
 extern crate sonmhub;
 
 use std::error::Error;
 
-use clap::{App, Arg};
-use sonmhub::{bot, config::Config, logging};
+use chrono::{DateTime, Utc};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use serde_json;
+use serde_yaml;
+use sonmhub::{audit::{self, Action, Filter}, bot, config::{self, Config, Overrides}, logging};
+
+/// Collects overrides from the environment and `--set` flags, in that order.
+fn overrides(matches: &ArgMatches) -> Result<Overrides, Box<Error>> {
+    let mut overrides = Overrides::from_env();
+    for arg in matches.values_of("set").into_iter().flat_map(|v| v) {
+        overrides.set(arg)?;
+    }
+
+    Ok(overrides)
+}
+
+/// Checks the configuration file, printing all found problems.
+fn check_config(path: &str, overrides: &Overrides) -> Result<(), Box<Error>> {
+    let report = config::check(path, overrides);
+    for diagnostic in report.diagnostics() {
+        println!("{}", diagnostic);
+    }
+
+    match report.errors() {
+        0 => {
+            println!("`{}` is valid, {} warning(s)", path, report.warnings());
+            Ok(())
+        }
+        v => Err(format!("`{}` has {} error(s), {} warning(s)", path, v, report.warnings()).into()),
+    }
+}
+
+/// Prints audit records matching the given filters, oldest first.
+fn query_audit(matches: &ArgMatches) -> Result<(), Box<Error>> {
+    let action = match matches.value_of("action") {
+        Some(action) => Some(serde_json::from_value::<Action>(serde_json::Value::String(action.into()))?),
+        None => None,
+    };
+
+    let number = match matches.value_of("pr") {
+        Some(number) => Some(number.parse()?),
+        None => None,
+    };
+
+    let since = match matches.value_of("since") {
+        Some(since) => Some(DateTime::parse_from_rfc3339(since)?.with_timezone(&Utc)),
+        None => None,
+    };
+
+    let filter = Filter {
+        repository: matches.value_of("repo").map(|v| v.to_owned()),
+        number,
+        head: matches.value_of("head").map(|v| v.to_owned()),
+        action,
+        since,
+    };
+
+    let path = matches.value_of("file").expect("audit log path has a default");
+    for record in audit::query(path, &filter)? {
+        if matches.is_present("json") {
+            println!("{}", serde_json::to_string(&record)?);
+        } else {
+            println!("{}", record);
+        }
+    }
+
+    Ok(())
+}
 
 fn main() -> Result<(), Box<Error>> {
     let matches = App::new(crate_name!())
         .version(crate_version!())
         .author(crate_authors!())
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(
             Arg::with_name("config")
                 .short("c")
@@ -20,17 +90,107 @@ fn main() -> Result<(), Box<Error>> {
                 .help("Path to the configuration file")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("set")
+                .short("s")
+                .long("set")
+                .value_name("KEY=VALUE")
+                .help("Overrides the config value, for example `merge.policy.approvals=2`")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("print-config")
+                .long("print-config")
+                .help("Prints the effective configuration with secrets redacted and exits"),
+        )
+        .subcommand(
+            SubCommand::with_name("check-config")
+                .about("Checks the configuration file and exits, non-zero on errors")
+                .arg(
+                    Arg::with_name("FILE")
+                        .required(true)
+                        .help("Path to the configuration file"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("audit")
+                .about("Prints recorded bot decisions, optionally filtered")
+                .arg(
+                    Arg::with_name("file")
+                        .long("file")
+                        .value_name("FILE")
+                        .default_value("audit.log")
+                        .help("Path to the audit log"),
+                )
+                .arg(
+                    Arg::with_name("repo")
+                        .long("repo")
+                        .value_name("OWNER/NAME")
+                        .help("Only decisions on PRs of the repository"),
+                )
+                .arg(
+                    Arg::with_name("pr")
+                        .long("pr")
+                        .value_name("NUMBER")
+                        .help("Only decisions on the PR"),
+                )
+                .arg(
+                    Arg::with_name("head")
+                        .long("head")
+                        .value_name("SHA")
+                        .help("Only decisions at the head with the given SHA prefix"),
+                )
+                .arg(
+                    Arg::with_name("action")
+                        .long("action")
+                        .value_name("ACTION")
+                        .possible_values(Action::NAMES)
+                        .help("Only decisions with the action taken"),
+                )
+                .arg(
+                    Arg::with_name("since")
+                        .long("since")
+                        .value_name("TIME")
+                        .help("Only decisions since the RFC 3339 time, like `2018-06-01T00:00:00Z`"),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Prints records as JSON lines"),
+                ),
+        )
         .get_matches();
 
+    if let Some(matches) = matches.subcommand_matches("check-config") {
+        let path = matches.value_of("FILE").expect("failed to extract configuration path");
+        return check_config(path, &overrides(matches)?);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("audit") {
+        return query_audit(matches);
+    }
+
     let path = matches
         .value_of("config")
         .expect("failed to extract configuration path");
 
-    let cfg = Config::load(path)?;
+    let overrides = overrides(&matches)?;
+    let cfg = Config::load(path, &overrides)?;
 
-    logging::init()?;
+    if matches.is_present("print-config") {
+        println!("{}", serde_yaml::to_string(&cfg)?);
+        return Ok(());
+    }
+
+    logging::init(cfg.logging())?;
+    for secret in cfg.secrets() {
+        logging::register_secret(secret);
+    }
 
-    match bot::run(cfg) {
+    match bot::run(cfg, path.into(), overrides) {
         0 => Ok(()),
         v => Err(format!("application exited with {} code", v).into()),
     }
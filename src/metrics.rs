@@ -0,0 +1,98 @@
+use actix_web::{HttpRequest, HttpResponse};
+use prometheus::{self, Encoder, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, TextEncoder};
+
+lazy_static! {
+    /// Webhook deliveries by event, action and processing result.
+    pub static ref WEBHOOK_DELIVERIES: IntCounterVec = register_int_counter_vec!(
+        "sonmhub_webhook_deliveries_total",
+        "Number of webhook deliveries by event, action and result.",
+        &["event", "action", "result"]
+    ).unwrap();
+
+    /// Webhook deliveries that were acknowledged, but skipped as duplicates.
+    pub static ref WEBHOOK_DUPLICATES: IntCounter = register_int_counter!(
+        "sonmhub_webhook_duplicates_total",
+        "Number of duplicate webhook deliveries."
+    ).unwrap();
+
+    /// GitHub API calls by endpoint and response status.
+    pub static ref GITHUB_API_CALLS: IntCounterVec = register_int_counter_vec!(
+        "sonmhub_github_api_calls_total",
+        "Number of GitHub API calls by endpoint and status.",
+        &["endpoint", "status"]
+    ).unwrap();
+
+    /// Remaining GitHub API requests in the current rate limit window.
+    pub static ref GITHUB_RATE_LIMIT_REMAINING: IntGauge = register_int_gauge!(
+        "sonmhub_github_rate_limit_remaining",
+        "Remaining GitHub API requests in the current rate limit window."
+    ).unwrap();
+
+    /// Number of approved pull requests waiting in the merge queue by
+    /// repository.
+    pub static ref MERGE_QUEUE_LENGTH: IntGaugeVec = register_int_gauge_vec!(
+        "sonmhub_merge_queue_length",
+        "Number of pull requests in the merge queue by repository.",
+        &["repository"]
+    ).unwrap();
+
+    /// Pull request merges by result, either `success` or `failure`.
+    pub static ref MERGES: IntCounterVec = register_int_counter_vec!(
+        "sonmhub_merges_total",
+        "Number of pull request merges by result.",
+        &["result"]
+    ).unwrap();
+
+    /// Events enqueued into the runtime channel, but not yet consumed.
+    pub static ref RUNTIME_CHANNEL_DEPTH: IntGauge = register_int_gauge!(
+        "sonmhub_runtime_channel_depth",
+        "Number of events waiting in the runtime channel."
+    ).unwrap();
+
+    /// Events rejected because the runtime channel was full.
+    pub static ref RUNTIME_CHANNEL_OVERFLOWS: IntCounter = register_int_counter!(
+        "sonmhub_runtime_channel_overflows_total",
+        "Number of events rejected because the runtime channel was full."
+    ).unwrap();
+}
+
+/// GitHub events we expect to receive.
+///
+/// The `X-GitHub-Event` header is read before the signature is verified, so
+/// it can not be used as a label value as is, otherwise anyone could blow up
+/// metrics cardinality.
+const EVENTS: &[&str] = &[
+    "check_run",
+    "check_suite",
+    "commit_comment",
+    "create",
+    "delete",
+    "issue_comment",
+    "issues",
+    "label",
+    "ping",
+    "pull_request",
+    "pull_request_review",
+    "pull_request_review_comment",
+    "push",
+    "status",
+];
+
+/// Maps the given event name into a label value with bounded cardinality.
+pub fn event_label(event: &str) -> &'static str {
+    EVENTS.iter().find(|v| **v == event).map(|v| *v).unwrap_or("other")
+}
+
+/// Exposes all registered metrics in the Prometheus text format.
+pub fn metrics<S>(_request: HttpRequest<S>) -> HttpResponse {
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+
+    match encoder.encode(&prometheus::gather(), &mut buffer) {
+        Ok(()) => HttpResponse::Ok().content_type(encoder.format_type()).body(buffer),
+        Err(err) => {
+            error!("failed to encode metrics: {}", err);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
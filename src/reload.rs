@@ -0,0 +1,207 @@
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use actix::actors::signal::{self, ProcessSignals, SignalType};
+use actix::{Actor, Addr, Arbiter, Context, Handler, Syn};
+use actix_web::{Error, HttpRequest, HttpResponse};
+use futures::prelude::*;
+use futures::sync::{mpsc, oneshot};
+use serde::Serialize;
+use serde_yaml;
+
+use config::{self, Config, ConfigError, Diagnostic, Overrides, Severity};
+use health::Health;
+use logging;
+use server::Event;
+
+/// An error that may occur while reloading the configuration, in which case
+/// the previous one stays in effect.
+#[derive(Debug)]
+pub enum ReloadError {
+    /// The new configuration has errors.
+    Invalid(Vec<Diagnostic>),
+    Load(ConfigError),
+    /// The runtime is either overloaded or shutting down.
+    Unavailable,
+}
+
+impl Display for ReloadError {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        match self {
+            ReloadError::Invalid(diagnostics) => {
+                fmt.write_str("invalid config")?;
+                for diagnostic in diagnostics {
+                    write!(fmt, "; {}", diagnostic)?;
+                }
+                Ok(())
+            }
+            ReloadError::Load(err) => err.fmt(fmt),
+            ReloadError::Unavailable => fmt.write_str("runtime is unavailable"),
+        }
+    }
+}
+
+impl error::Error for ReloadError {}
+
+struct Inner {
+    path: PathBuf,
+    overrides: Overrides,
+    tx: Mutex<mpsc::Sender<Event>>,
+    health: Health,
+    /// Configuration in effect. The lock also serializes reloads.
+    current: Mutex<Config>,
+}
+
+/// Reloads the configuration file on demand.
+///
+/// Policies, served repositories and log levels are applied to the running
+/// application, while other settings require restart.
+#[derive(Clone)]
+pub struct Reloader {
+    inner: Arc<Inner>,
+}
+
+impl Reloader {
+    pub fn new(path: PathBuf, overrides: Overrides, cfg: Config, tx: mpsc::Sender<Event>, health: Health) -> Self {
+        let inner = Inner {
+            path,
+            overrides,
+            tx: Mutex::new(tx),
+            health,
+            current: Mutex::new(cfg),
+        };
+
+        Self { inner: Arc::new(inner) }
+    }
+
+    /// Re-reads and checks the configuration file, applying it when valid.
+    ///
+    /// Returns the hash of the configuration in effect.
+    pub fn reload(&self) -> Result<String, ReloadError> {
+        let inner = &self.inner;
+        let mut current = inner.current.lock().expect("config lock is poisoned");
+
+        let report = config::check(&inner.path, &inner.overrides);
+        if report.errors() > 0 {
+            let errors = report
+                .diagnostics()
+                .iter()
+                .filter(|v| v.severity() == Severity::Error)
+                .cloned()
+                .collect();
+            return Err(ReloadError::Invalid(errors));
+        }
+
+        let cfg = Config::load(&inner.path, &inner.overrides).map_err(ReloadError::Load)?;
+        if cfg.hash() == current.hash() {
+            info!("configuration is unchanged");
+            return Ok(cfg.hash().into());
+        }
+
+        let sections = [
+            ("network", differs(current.network(), cfg.network())),
+            ("hook", differs(current.hook(), cfg.hook())),
+            ("deliveries", differs(current.deliveries(), cfg.deliveries())),
+            ("github", differs(current.github(), cfg.github())),
+            ("logging.format", differs(&current.logging().format(), &cfg.logging().format())),
+            ("logging.target", differs(current.logging().target(), cfg.logging().target())),
+        ];
+        for (section, _) in sections.iter().filter(|(_, changed)| *changed) {
+            warn!("changed `{}` settings require restart", section);
+        }
+
+        inner
+            .tx
+            .lock()
+            .expect("sender lock is poisoned")
+            .try_send(Event::Reconfigure(cfg.merge().clone()))
+            .map_err(|_| ReloadError::Unavailable)?;
+
+        for secret in cfg.secrets() {
+            logging::register_secret(secret);
+        }
+        logging::reload(cfg.logging());
+        inner.health.set_config(cfg.hash());
+
+        info!("reloaded configuration {}", cfg.hash());
+        *current = cfg;
+
+        Ok(current.hash().into())
+    }
+
+    /// Reloads the configuration on a separate thread, since reading and
+    /// checking files blocks, which neither HTTP workers nor the system
+    /// arbiter may do. Failures are logged as well.
+    pub fn spawn(&self) -> oneshot::Receiver<Result<String, ReloadError>> {
+        let reloader = self.clone();
+        let (tx, rx) = oneshot::channel();
+        thread::spawn(move || {
+            let result = reloader.reload();
+            if let Err(ref err) = result {
+                error!("failed to reload configuration: {}", err);
+            }
+            // The requester may have gone away already.
+            let _ = tx.send(result);
+        });
+
+        rx
+    }
+
+    /// Reloads the configuration each time the process receives `SIGHUP`.
+    ///
+    /// Must be called within a running system.
+    pub fn listen(&self) {
+        let addr: Addr<Syn, _> = Hangup(self.clone()).start();
+        Arbiter::system_registry()
+            .get::<ProcessSignals>()
+            .do_send(signal::Subscribe(addr.recipient()));
+    }
+}
+
+/// Compares serialized representations, since config sections are not
+/// comparable. Secrets are redacted, so their changes are not detected.
+fn differs<T: Serialize>(prev: &T, next: &T) -> bool {
+    serde_yaml::to_string(prev).ok() != serde_yaml::to_string(next).ok()
+}
+
+struct Hangup(Reloader);
+
+impl Actor for Hangup {
+    type Context = Context<Self>;
+}
+
+impl Handler<signal::Signal> for Hangup {
+    type Result = ();
+
+    fn handle(&mut self, signal: signal::Signal, _: &mut Context<Self>) {
+        if let SignalType::Hup = signal.0 {
+            info!("reloading configuration on SIGHUP");
+            self.0.spawn();
+        }
+    }
+}
+
+/// Reloads the configuration, responding with either the new config hash
+/// or errors that prevented the reload.
+pub fn reload<S: AsRef<Reloader>>(request: HttpRequest<S>) -> impl Future<Item = HttpResponse, Error = Error> {
+    request.state().as_ref().spawn().then(|result| {
+        let response = match result {
+            Ok(Ok(hash)) => HttpResponse::Ok().json(json!({"config": hash})),
+            Ok(Err(ReloadError::Invalid(diagnostics))) => {
+                let errors: Vec<String> = diagnostics.iter().map(|v| v.to_string()).collect();
+                HttpResponse::UnprocessableEntity().json(json!({"errors": errors}))
+            }
+            Ok(Err(err @ ReloadError::Load(..))) => {
+                HttpResponse::UnprocessableEntity().json(json!({"errors": [err.to_string()]}))
+            }
+            Ok(Err(ReloadError::Unavailable)) => HttpResponse::ServiceUnavailable().finish(),
+            // The reloading thread panicked.
+            Err(..) => HttpResponse::InternalServerError().finish(),
+        };
+
+        Ok::<_, Error>(response)
+    })
+}
@@ -0,0 +1,233 @@
+use std::collections::VecDeque;
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::prelude::*;
+use futures::stream;
+use tokio::timer::Interval;
+
+use bot::Router;
+use logging::{self, Context};
+use metrics;
+use server::{Event, Hook};
+
+/// An error that may occur while the runtime persists incoming events.
+#[derive(Debug)]
+pub enum RuntimeError {
+    /// Failed to open the event log.
+    Open(PathBuf, io::Error),
+    /// Failed to append an event into the event log.
+    Write(io::Error),
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        match self {
+            RuntimeError::Open(path, err) => {
+                write!(fmt, "failed to open event log `{}`: {}", path.display(), err)
+            }
+            RuntimeError::Write(err) => write!(fmt, "failed to write event log: {}", err),
+        }
+    }
+}
+
+impl error::Error for RuntimeError {}
+
+#[derive(Debug)]
+struct StatusInner {
+    healthy: AtomicBool,
+    depth: AtomicUsize,
+    overflows: AtomicUsize,
+}
+
+/// Runtime status shared between the HTTP server and the runtime itself.
+///
+/// The server consults it before accepting events, which allows to reject
+/// hooks while the event sink is unable to persist them.
+#[derive(Clone, Debug)]
+pub struct Status {
+    inner: Arc<StatusInner>,
+}
+
+impl Status {
+    pub fn new() -> Self {
+        let inner = StatusInner {
+            healthy: AtomicBool::new(true),
+            depth: AtomicUsize::new(0),
+            overflows: AtomicUsize::new(0),
+        };
+
+        Self { inner: Arc::new(inner) }
+    }
+
+    /// Returns `true` if the last attempt to persist an event succeeded.
+    pub fn is_healthy(&self) -> bool {
+        self.inner.healthy.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of events that were enqueued, but not yet consumed.
+    pub fn depth(&self) -> usize {
+        self.inner.depth.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of events rejected because the queue was full.
+    pub fn overflows(&self) -> usize {
+        self.inner.overflows.load(Ordering::SeqCst)
+    }
+
+    /// Marks an event as enqueued.
+    ///
+    /// Must be called before the event is actually sent, otherwise the
+    /// runtime may consume it first, underflowing the depth counter.
+    pub fn enqueued(&self) {
+        self.inner.depth.fetch_add(1, Ordering::SeqCst);
+        metrics::RUNTIME_CHANNEL_DEPTH.inc();
+    }
+
+    /// Marks a previously enqueued event as not being sent.
+    pub fn cancelled(&self) {
+        self.inner.depth.fetch_sub(1, Ordering::SeqCst);
+        metrics::RUNTIME_CHANNEL_DEPTH.dec();
+    }
+
+    /// Marks a previously enqueued event as rejected because of the queue
+    /// overflow.
+    pub fn overflowed(&self) {
+        self.cancelled();
+        self.inner.overflows.fetch_add(1, Ordering::SeqCst);
+        metrics::RUNTIME_CHANNEL_OVERFLOWS.inc();
+    }
+
+    fn consumed(&self) {
+        self.cancelled();
+    }
+
+    fn set_healthy(&self, healthy: bool) {
+        self.inner.healthy.store(healthy, Ordering::SeqCst);
+    }
+}
+
+/// How often the runtime retries to persist an event after a failure.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+enum Tick {
+    Event(Event),
+    Retry,
+    Closed,
+}
+
+/// Consumes webhook events, appending each of them into the event log and
+/// routing them to repository workers.
+pub struct Runtime<S> {
+    stream: S,
+    log: File,
+    status: Status,
+    router: Router,
+    /// Hooks that are not persisted yet, oldest first.
+    pending: VecDeque<Hook>,
+}
+
+impl<S> Runtime<S>
+where
+    S: Stream<Item = Event, Error = ()> + 'static,
+{
+    pub fn new<P: AsRef<Path>>(
+        path: P,
+        stream: S,
+        status: Status,
+        router: Router,
+    ) -> Result<Self, RuntimeError> {
+        let path = path.as_ref();
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| RuntimeError::Open(path.into(), err))?;
+
+        Ok(Self {
+            stream,
+            log,
+            status,
+            router,
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Runs the runtime until all senders are dropped.
+    ///
+    /// Failing to persist an event does not stop the runtime. Instead it is
+    /// marked as unhealthy, so the server stops accepting new hooks, and the
+    /// failed event is retried periodically along with the ones received
+    /// meanwhile, in order. This allows to recover, for example, after the
+    /// disk space is freed.
+    #[async]
+    pub fn run(mut self) -> Result<(), RuntimeError> {
+        let ticks = Interval::new(Instant::now() + RETRY_INTERVAL, RETRY_INTERVAL)
+            .map(|_| Tick::Retry)
+            .map_err(|err| error!("runtime timer failed: {}", err));
+        let stream = self.stream
+            .map(Tick::Event)
+            .chain(stream::once(Ok(Tick::Closed)))
+            .select(ticks);
+
+        #[async]
+        for tick in stream {
+            match tick {
+                Tick::Event(Event::Hook(hook)) => {
+                    self.status.consumed();
+                    self.router.route(hook.clone());
+                    self.pending.push_back(hook);
+                }
+                Tick::Event(Event::Reconfigure(cfg)) => {
+                    self.router.reconfigure(cfg);
+                    continue;
+                }
+                Tick::Retry => {}
+                Tick::Closed => break,
+            }
+
+            self.flush();
+        }
+
+        self.flush();
+        if !self.pending.is_empty() {
+            error!("{} events were not persisted before shutdown", self.pending.len());
+        }
+
+        Ok(())
+    }
+
+    /// Persists pending hooks in order, stopping at the first failure.
+    fn flush(&mut self) {
+        while let Some(hook) = self.pending.pop_front() {
+            if let Err(err) = Runtime::<S>::write(&mut self.log, &hook) {
+                error!("{}", err);
+                self.status.set_healthy(false);
+                self.pending.push_front(hook);
+                return;
+            }
+        }
+
+        if !self.status.is_healthy() {
+            info!("event sink has recovered");
+            self.status.set_healthy(true);
+        }
+    }
+
+    fn write(log: &mut File, hook: &Hook) -> Result<(), RuntimeError> {
+        let context = Context::default().with_delivery(hook.delivery.clone());
+
+        logging::with_context(&context, || {
+            debug!("persisting `{}` event", hook.event);
+            log.write_all(hook.payload.to_string().as_bytes()).map_err(RuntimeError::Write)?;
+            log.write_all(b"\n").map_err(RuntimeError::Write)?;
+            log.flush().map_err(RuntimeError::Write)
+        })
+    }
+}
@@ -1,7 +1,60 @@
-use actix_web::{http::StatusCode, HttpResponse};
+use std::fmt::{self, Debug, Formatter};
+
+use actix_web::{http::{header::{HeaderMap, AUTHORIZATION}, StatusCode}, HttpResponse};
 use crypto::{
     hmac::Hmac, mac::{Mac, MacResult}, sha1::Sha1,
 };
+use serde::{Serialize, Serializer};
+
+const REDACTED: &str = "<redacted>";
+
+/// A string that must never appear in logs, for example, an access token.
+///
+/// Its `Debug` and `Serialize` implementations print a placeholder instead of
+/// the value, which is only accessible via `expose`.
+#[derive(Clone, Deserialize, PartialEq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new<T: Into<String>>(value: T) -> Self {
+        Secret(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        match self {
+            Secret(v) => v,
+        }
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        fmt.write_str(REDACTED)
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, se: S) -> Result<S::Ok, S::Error> {
+        se.serialize_str(REDACTED)
+    }
+}
+
+/// Debug representation of HTTP headers with credentials redacted.
+pub struct RedactedHeaders<'a>(pub &'a HeaderMap);
+
+impl<'a> Debug for RedactedHeaders<'a> {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), fmt::Error> {
+        let mut map = fmt.debug_map();
+        for (name, value) in self.0 {
+            if name == AUTHORIZATION {
+                map.entry(name, &REDACTED);
+            } else {
+                map.entry(name, value);
+            }
+        }
+        map.finish()
+    }
+}
 
 pub struct Forbidden;
 
@@ -1,27 +1,64 @@
-use std::borrow::Cow;
 use std::error;
 use std::fmt::{self, Display, Formatter};
 use std::str;
+use std::sync::{Arc, Mutex};
 
 use actix_web::{http::StatusCode, Error, HttpMessage, HttpRequest, HttpResponse, ResponseError};
 use futures::prelude::*;
 use rustc_hex::{FromHex};
 use serde_json::{from_slice, Value};
 
-use secure::{self, Forbidden};
+use allowlist::Allowlist;
+use config::MergeBotConfig;
+use deliveries::Deliveries;
+use health::Health;
+use logging::{self, Context};
+use metrics;
+use reload::Reloader;
+use runtime::Status;
+use secure::{self, Forbidden, Secret};
 
+const X_GITHUB_EVENT: &str = "X-GitHub-Event";
 const X_HUB_SIGNATURE: &str = "X-Hub-Signature";
+const X_GITHUB_DELIVERY: &str = "X-GitHub-Delivery";
+const X_FORWARDED_FOR: &str = "X-Forwarded-For";
 
 enum ErrorKind {
+    SourceNotAllowed,
     GitHubEventHeaderRequired,
     GitHubSignatureHeaderRequired,
     InvalidSignatureHeader,
     UnsupportedHMACMethod,
+    InvalidSignature,
+    EventSinkUnavailable,
+    EventQueueOverflow,
+}
+
+impl ErrorKind {
+    /// Returns the value of the `result` label for delivery metrics.
+    fn label(&self) -> &'static str {
+        match self {
+            ErrorKind::SourceNotAllowed => "source_not_allowed",
+            ErrorKind::GitHubEventHeaderRequired => "missing_event",
+            ErrorKind::GitHubSignatureHeaderRequired => "missing_signature",
+            ErrorKind::InvalidSignatureHeader => "invalid_signature_header",
+            ErrorKind::UnsupportedHMACMethod => "unsupported_hmac",
+            ErrorKind::InvalidSignature => "invalid_signature",
+            ErrorKind::EventSinkUnavailable => "sink_unavailable",
+            ErrorKind::EventQueueOverflow => "queue_overflow",
+        }
+    }
 }
 
 impl Into<HttpResponse> for ErrorKind {
     fn into(self) -> HttpResponse {
         let (code, err) = match self {
+            ErrorKind::InvalidSignature => {
+                return Forbidden.into()
+            }
+            ErrorKind::SourceNotAllowed => {
+                (StatusCode::FORBIDDEN, "source address is not allowed")
+            }
             ErrorKind::GitHubEventHeaderRequired => {
                 (StatusCode::NOT_FOUND, "header `X-GitHub-Event` is required")
             }
@@ -34,6 +71,12 @@ impl Into<HttpResponse> for ErrorKind {
             ErrorKind::UnsupportedHMACMethod => {
                 (StatusCode::UNAUTHORIZED, "unsupported HMAC method")
             }
+            ErrorKind::EventSinkUnavailable => {
+                (StatusCode::SERVICE_UNAVAILABLE, "event sink is unavailable")
+            }
+            ErrorKind::EventQueueOverflow => {
+                (StatusCode::SERVICE_UNAVAILABLE, "event queue is full")
+            }
         };
 
         HttpResponse::build(code).json(json!({"error": err}))
@@ -54,47 +97,183 @@ impl error::Error for ShuttingDown {}
 
 impl ResponseError for ShuttingDown {}
 
+/// Outcome of a single webhook delivery.
+enum Outcome {
+    Ping,
+    Accepted(String),
+    Duplicate(String),
+    Rejected(ErrorKind),
+}
+
+impl Outcome {
+    fn action(&self) -> &str {
+        match self {
+            Outcome::Accepted(action) | Outcome::Duplicate(action) => action,
+            Outcome::Ping | Outcome::Rejected(..) => "",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Outcome::Ping => "ping",
+            Outcome::Accepted(..) => "accepted",
+            Outcome::Duplicate(..) => "duplicate",
+            Outcome::Rejected(kind) => kind.label(),
+        }
+    }
+}
+
+impl Into<HttpResponse> for Outcome {
+    fn into(self) -> HttpResponse {
+        match self {
+            Outcome::Ping | Outcome::Accepted(..) | Outcome::Duplicate(..) => {
+                HttpResponse::Ok().finish()
+            }
+            Outcome::Rejected(kind) => kind.into(),
+        }
+    }
+}
+
+/// A webhook delivery with verified signature.
+#[derive(Clone, Debug)]
+pub struct Hook {
+    /// Event name from the `X-GitHub-Event` header.
+    pub event: String,
+    /// Delivery GUID from the `X-GitHub-Delivery` header.
+    pub delivery: Option<String>,
+    pub payload: Value,
+}
+
 #[derive(Clone, Debug)]
 pub enum Event {
-    Log(Value),
+    Hook(Hook),
+    /// Reloaded merge settings, which must be applied to the routed
+    /// repositories.
+    Reconfigure(MergeBotConfig),
+}
+
+/// Application state that the hook server shares with the rest of the bot.
+#[derive(Clone, Debug)]
+pub struct Shared {
+    pub status: Status,
+    pub deliveries: Deliveries,
+    pub health: Health,
+    pub reloader: Reloader,
+    pub allowlist: Allowlist,
 }
 
 #[derive(Clone, Debug)]
 pub struct Server<W> {
-    tx: W,
-    secret: Cow<'static, [u8]>,
+    /// Sender shared by all requests, since each clone of a bounded sender
+    /// gets its own guaranteed slot, which makes the queue unbounded.
+    tx: Arc<Mutex<W>>,
+    shared: Shared,
+    secret: Secret,
+    /// Maximum size of a hook payload in bytes.
+    max_payload: usize,
+}
+
+impl<W> AsRef<Health> for Server<W> {
+    fn as_ref(&self) -> &Health {
+        &self.shared.health
+    }
+}
+
+impl<W> AsRef<Reloader> for Server<W> {
+    fn as_ref(&self) -> &Reloader {
+        &self.shared.reloader
+    }
 }
 
 impl<W> Server<W>
 where
     W: Sink<SinkItem = Event> + Clone + 'static,
 {
-    pub fn new(tx: W) -> Self {
+    pub fn new(tx: W, secret: Secret, max_payload: usize, shared: Shared) -> Self {
         Self {
-            tx,
-            secret: b"<secret>"[..].into(),
+            tx: Arc::new(Mutex::new(tx)),
+            shared,
+            secret,
+            max_payload,
         }
     }
 
     fn verify(&self, data: &[u8], signature: &[u8]) -> Result<(), Forbidden> {
-        secure::verify(data, &self.secret, signature)
+        secure::verify(data, self.secret.expose().as_bytes(), signature)
     }
 
     pub fn index(request: HttpRequest<Server<W>>) -> impl Future<Item = HttpResponse, Error = Error> {
-        request.state().clone().execute(request)
+        let event = request.headers()
+            .get(X_GITHUB_EVENT)
+            .and_then(|v| v.to_str().ok())
+            .map(metrics::event_label)
+            .unwrap_or("none");
+
+        let delivery = request.headers()
+            .get(X_GITHUB_DELIVERY)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_owned());
+        let context = Context::request().with_delivery(delivery);
+
+        let future = request.state().clone().execute(request);
+        logging::scoped(context, future).then(move |result| {
+            {
+                let (action, label) = match result {
+                    Ok(ref outcome) => (outcome.action(), outcome.label()),
+                    Err(..) => ("", "error"),
+                };
+
+                metrics::WEBHOOK_DELIVERIES.with_label_values(&[event, action, label]).inc();
+            }
+
+            result.map(Into::into)
+        })
+    }
+
+    /// Checks whether the client the request came from is allowed to
+    /// deliver hooks.
+    fn is_allowed(&self, request: &HttpRequest<Server<W>>) -> bool {
+        if !self.shared.allowlist.is_enabled() {
+            return true;
+        }
+
+        // Proxies may append their hops either to the existing header or as
+        // a separate header line. Values that are not text fail to parse as
+        // addresses, so the client is unknown then.
+        let forwarded: Vec<&str> = request
+            .headers()
+            .get_all(X_FORWARDED_FOR)
+            .iter()
+            .map(|v| v.to_str().unwrap_or(""))
+            .collect();
+        match self.shared.allowlist.client(request.peer_addr().map(|v| v.ip()), &forwarded) {
+            Some(addr) if self.shared.allowlist.allows(addr) => true,
+            Some(addr) => {
+                warn!("rejected hook from {}, which is not in the allowlist", addr);
+                false
+            }
+            None => {
+                warn!("rejected hook from unknown source");
+                false
+            }
+        }
     }
 
     #[async]
-    fn execute(self, request: HttpRequest<Server<W>>) -> Result<HttpResponse, Error> {
-        match request.headers().get("X-GitHub-Event").map(|v| v.as_bytes()) {
+    fn execute(self, request: HttpRequest<Server<W>>) -> Result<Outcome, Error> {
+        if !self.is_allowed(&request) {
+            return Ok(Outcome::Rejected(ErrorKind::SourceNotAllowed))
+        }
+
+        let event = match request.headers().get(X_GITHUB_EVENT).map(|v| v.as_bytes()) {
             Some(b"ping") => {
-                return Ok(HttpResponse::Ok().finish())
+                return Ok(Outcome::Ping)
             }
             Some(event) => {
-                event.to_owned()
+                String::from_utf8_lossy(event).into_owned()
             }
             None => {
-                return Ok(ErrorKind::GitHubEventHeaderRequired.into())
+                return Ok(Outcome::Rejected(ErrorKind::GitHubEventHeaderRequired))
             }
         };
 
@@ -105,29 +284,133 @@ where
                         signature.to_owned()
                     }
                     None => {
-                        return Ok(ErrorKind::InvalidSignatureHeader.into())
+                        return Ok(Outcome::Rejected(ErrorKind::InvalidSignatureHeader))
                     }
                 }
             },
             Some(..) => {
-                return Ok(ErrorKind::UnsupportedHMACMethod.into())
+                return Ok(Outcome::Rejected(ErrorKind::UnsupportedHMACMethod))
             }
             None => {
-                return Ok(ErrorKind::GitHubSignatureHeaderRequired.into())
+                return Ok(Outcome::Rejected(ErrorKind::GitHubSignatureHeaderRequired))
             }
         };
 
-        let body = await!(request.body())?;
+        let delivery = request.headers()
+            .get(X_GITHUB_DELIVERY)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_owned());
+
+        let body = await!(request.body().limit(self.max_payload))?;
 
-        if let Err(err) = self.verify(&body, &signature) {
-            return Ok(err.into())
+        if let Err(Forbidden) = self.verify(&body, &signature) {
+            return Ok(Outcome::Rejected(ErrorKind::InvalidSignature))
         }
 
-        let tx = self.tx.clone();
+        if !self.shared.status.is_healthy() {
+            return Ok(Outcome::Rejected(ErrorKind::EventSinkUnavailable))
+        }
 
         let body: Value = from_slice(&body)?;
-        await!(tx.send(Event::Log(body))).or(Err(ShuttingDown))?;
+        let action = body["action"].as_str().unwrap_or("").to_owned();
+
+        if let Some(ref delivery) = delivery {
+            if !self.shared.deliveries.insert(delivery) {
+                info!("skipped duplicate delivery {}", delivery);
+                return Ok(Outcome::Duplicate(action))
+            }
+        }
+
+        let hook = Hook {
+            event,
+            delivery: delivery.clone(),
+            payload: body,
+        };
+        let outcome = enqueue(&self.tx, &self.shared.status, Event::Hook(hook), action);
+
+        // Forget the delivery if it was not enqueued, so that its redelivery
+        // will be processed.
+        if let Some(ref delivery) = delivery {
+            match outcome {
+                Ok(Outcome::Accepted(..)) => {}
+                _ => self.shared.deliveries.remove(delivery),
+            }
+        }
+
+        outcome
+    }
+}
+
+/// Tries to enqueue the given event without waiting for a free slot.
+///
+/// A full queue means that the runtime does not keep up with incoming
+/// hooks, so we reject the hook instead of holding the connection.
+fn enqueue<W>(tx: &Mutex<W>, status: &Status, event: Event, action: String) -> Result<Outcome, Error>
+where
+    W: Sink<SinkItem = Event>,
+{
+    let mut tx = tx.lock().expect("sender lock is poisoned");
+
+    status.enqueued();
+    match tx.start_send(event) {
+        Ok(AsyncSink::Ready) => Ok(Outcome::Accepted(action)),
+        Ok(AsyncSink::NotReady(..)) => {
+            status.overflowed();
+            warn!("event queue is full, rejected {} events so far", status.overflows());
+            Ok(Outcome::Rejected(ErrorKind::EventQueueOverflow))
+        }
+        Err(..) => {
+            status.cancelled();
+            Err(ShuttingDown.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use futures::future;
+    use futures::prelude::*;
+    use futures::sync::mpsc;
+    use serde_json::Value;
+
+    use runtime::Status;
+
+    use super::{enqueue, ErrorKind, Event, Hook, Outcome};
+
+    fn hook() -> Event {
+        Event::Hook(Hook {
+            event: "push".into(),
+            delivery: None,
+            payload: Value::Null,
+        })
+    }
+
+    #[test]
+    fn enqueue_rejects_events_when_queue_is_full() {
+        // A bounded channel holds its buffer plus a slot per sender.
+        let (tx, _rx) = mpsc::channel(1);
+        let tx = Mutex::new(tx);
+        let status = Status::new();
+
+        future::lazy(|| {
+            for _ in 0..2 {
+                match enqueue(&tx, &status, hook(), "opened".into()) {
+                    Ok(Outcome::Accepted(..)) => {}
+                    _ => panic!("event must be accepted"),
+                }
+            }
+
+            match enqueue(&tx, &status, hook(), "opened".into()) {
+                Ok(Outcome::Rejected(ErrorKind::EventQueueOverflow)) => {}
+                _ => panic!("event must be rejected"),
+            }
+            Ok::<_, ()>(())
+        }).wait()
+            .unwrap();
 
-        Ok(HttpResponse::Ok().finish())
+        assert_eq!(2, status.depth());
+        assert_eq!(1, status.overflows());
     }
 }
@@ -0,0 +1,113 @@
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Instant, SystemTime};
+
+use futures::prelude::*;
+use openssl::error::ErrorStack;
+use openssl::ssl::{SniError, SslAcceptor, SslAcceptorBuilder, SslFiletype, SslMethod, SslVerifyMode};
+use tokio::timer::Interval;
+
+use config::TlsConfig;
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|v| v.modified()).ok()
+}
+
+/// Certificates of the HTTPS listener, which are reloaded when their files
+/// change.
+///
+/// The server is bound with an acceptor built once, so renewed certificates
+/// reach new connections only through the SNI callback, which switches the
+/// handshake to the most recently loaded context. Clients that send no
+/// server name, like ones connecting by an IP address, keep getting the
+/// certificate loaded at startup until restart. Changes of the client CA
+/// always require restart, because the verification store is not switched
+/// along with the context.
+#[derive(Clone)]
+pub struct Certificates {
+    cfg: TlsConfig,
+    current: Arc<RwLock<SslAcceptor>>,
+    /// Modification times of the certificate and the key currently loaded.
+    modified: Arc<Mutex<(Option<SystemTime>, Option<SystemTime>)>>,
+}
+
+impl Certificates {
+    pub fn new(cfg: TlsConfig) -> Result<Self, ErrorStack> {
+        let modified = (modified(cfg.cert()), modified(cfg.key()));
+        let acceptor = Certificates::builder(&cfg)?.build();
+
+        let certificates = Self {
+            cfg,
+            current: Arc::new(RwLock::new(acceptor)),
+            modified: Arc::new(Mutex::new(modified)),
+        };
+
+        Ok(certificates)
+    }
+
+    fn builder(cfg: &TlsConfig) -> Result<SslAcceptorBuilder, ErrorStack> {
+        let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+        builder.set_private_key_file(cfg.key(), SslFiletype::PEM)?;
+        builder.set_certificate_chain_file(cfg.cert())?;
+        builder.check_private_key()?;
+
+        if let Some(path) = cfg.client_ca() {
+            builder.set_ca_file(path)?;
+            builder.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+        }
+
+        Ok(builder)
+    }
+
+    /// Returns the acceptor to bind the server with, which switches
+    /// handshakes with SNI to reloaded certificates.
+    pub fn acceptor(&self) -> Result<SslAcceptorBuilder, ErrorStack> {
+        let mut builder = Certificates::builder(&self.cfg)?;
+
+        let current = self.current.clone();
+        builder.set_servername_callback(move |ssl, _alert| {
+            let acceptor = current.read().expect("certificates lock is poisoned");
+            ssl.set_ssl_context(acceptor.context()).map_err(|_| SniError::ALERT_FATAL)
+        });
+
+        Ok(builder)
+    }
+
+    /// Loads certificates again if their files have changed since the last
+    /// load. Invalid files are reported, keeping the previous certificates.
+    fn reload(&self) {
+        let next = (modified(self.cfg.cert()), modified(self.cfg.key()));
+
+        let mut modified = self.modified.lock().expect("certificates lock is poisoned");
+        if *modified == next {
+            return;
+        }
+
+        match Certificates::builder(&self.cfg) {
+            Ok(builder) => {
+                *self.current.write().expect("certificates lock is poisoned") = builder.build();
+                *modified = next;
+                info!("reloaded TLS certificate `{}`", self.cfg.cert().display());
+            }
+            Err(err) => {
+                error!("failed to reload TLS certificate `{}`: {}", self.cfg.cert().display(), err);
+            }
+        }
+    }
+
+    /// Periodically checks certificate files for changes.
+    #[async]
+    pub fn watch(self) -> Result<(), ()> {
+        let interval = self.cfg.reload_interval();
+        let ticks = Interval::new(Instant::now() + interval, interval)
+            .map_err(|err| error!("certificates timer failed: {}", err));
+
+        #[async]
+        for _ in ticks {
+            self.reload();
+        }
+
+        Ok(())
+    }
+}